@@ -0,0 +1,114 @@
+//! `#[derive(EventPayload)]` for `sse-oled-controller`, re-exported as `sse_oled_controller::payload::EventPayload`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr, Type};
+
+/// Turns a struct with named fields into event data. Every field goes into the context frame
+/// under its name, except the one marked `#[payload(value)]` which becomes the value of the event.
+///
+/// - `#[payload(value)]` - the event value, 0 when no field has it
+/// - `#[payload(rename = "key")]` - use another frame key than the field name
+///
+/// `Option` fields are left out of the frame while they are `None`.
+/// The other fields must be `Clone` and convert into `EventValue`.
+#[proc_macro_derive(EventPayload, attributes(payload))]
+pub fn derive_event_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+struct FieldOptions {
+    value: bool,
+    rename: Option<String>
+}
+
+fn field_options(field: &Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions {
+        value: false,
+        rename: None
+    };
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("payload")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("value") {
+                options.value = true;
+                Ok(())
+            } else if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `value` or `rename = \"...\"`"))
+            }
+        })?;
+    }
+
+    Ok(options)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|segment| segment.ident == "Option"),
+        _ => false
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "EventPayload can only be derived for structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input.ident, "EventPayload needs a struct with named fields"));
+    };
+
+    let mut value = None;
+    let mut keys = Vec::new();
+    let mut inserts = Vec::new();
+
+    for field in &fields.named {
+        let options = field_options(field)?;
+        let ident = field.ident.as_ref().unwrap();
+
+        if options.value {
+            if value.is_some() {
+                return Err(syn::Error::new_spanned(field, "only one field can be the `value`"));
+            }
+            value = Some(quote!(::core::clone::Clone::clone(&self.#ident)));
+            continue;
+        }
+
+        let key = options.rename.unwrap_or_else(|| ident.to_string());
+        if keys.contains(&key) {
+            return Err(syn::Error::new_spanned(field, format!("duplicate frame key `{}`", key)));
+        }
+
+        inserts.push(if is_option(&field.ty) {
+            quote! {
+                if let ::core::option::Option::Some(value) = &self.#ident {
+                    data = data.with(#key, ::core::clone::Clone::clone(value));
+                }
+            }
+        } else {
+            quote!(data = data.with(#key, ::core::clone::Clone::clone(&self.#ident));)
+        });
+        keys.push(key);
+    }
+
+    let value = value.unwrap_or(quote!(0));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::sse_oled_controller::payload::EventPayload for #name #ty_generics #where_clause {
+            const KEYS: &'static [&'static str] = &[#(#keys),*];
+
+            fn to_event_data(&self) -> ::sse_oled_controller::api_types::EventData {
+                #[allow(unused_mut)]
+                let mut data = ::sse_oled_controller::api_types::EventData::new(#value);
+                #(#inserts)*
+                data
+            }
+        }
+    })
+}
@@ -1,8 +1,14 @@
-use serde::{Deserialize, Serialize};
-use log::debug;
-use crate::api_types::{C2SGameCreate, C2SGameEventBind, C2SGameEventCreate, C2SGameEventRemove, C2SGameRemove, C2SHeartBeat, C2STriggerEvent, EventData};
-use crate::types::ScreenHandler;
+//! The HTTP client of the engine.
 
+use serde::{Deserialize, Serialize};
+use log::{debug, warn};
+use crate::api_types::{C2SGameCreate, C2SGameEventBind, C2SGameEventCreate, C2SGameEventRemove, C2SGameRemove, C2SGoLispHandlers, C2SHeartBeat, C2STriggerEvent, EventData};
+use crate::bitmap::{self, KeyboardBitmap};
+use crate::payload::EventPayload;
+use crate::types::Handler;
+
+/// Talks to the engine on behalf of one game.
+/// Call `setup` first, the other requests are sent for the game it registered.
 pub struct SSEngineAPI {
     server: String,
     http: reqwest::Client,
@@ -17,18 +23,55 @@ pub struct CoreProperties {
 }
 
 impl SSEngineAPI {
+    /// Environment variable with the path of `coreProps.json`, for an engine installed elsewhere,
+    /// or a compatible server on a platform without an official engine.
+    pub const CORE_PROPS_ENV: &'static str = "SSE_CORE_PROPS";
+
     #[cfg(target_os = "windows")]
     const DEFAULT_CORE_PROP_LOCATION: &'static str = "%PROGRAMDATA%/SteelSeries/SteelSeries Engine 3/coreProps.json";
     #[cfg(target_os = "macos")]
     const DEFAULT_CORE_PROP_LOCATION: &'static str = "/Library/Application Support/SteelSeries Engine 3/coreProps.json";
 
+    /// Where to read `coreProps.json` from, panics on platforms without an engine when the variable isn't set.
+    fn core_props_path() -> String {
+        if let Some(path) = std::env::var_os(SSEngineAPI::CORE_PROPS_ENV) {
+            return path.to_string_lossy().into_owned();
+        }
+
+        #[cfg(target_os = "windows")]
+        return {
+            let app_data = std::env::var("PROGRAMDATA").expect("No PROGRAMDATA directory");
+            SSEngineAPI::DEFAULT_CORE_PROP_LOCATION.replace("%PROGRAMDATA%", &app_data)
+        };
+        #[cfg(target_os = "macos")]
+        return SSEngineAPI::DEFAULT_CORE_PROP_LOCATION.to_string();
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        panic!("There is no engine on this platform, give the address of the server or set {} to the path of its coreProps.json",
+            SSEngineAPI::CORE_PROPS_ENV);
+    }
+
+    /// Registers the game and starts sending heartbeats, so that the engine doesn't drop it.
     pub async fn setup(&mut self, game: C2SGameCreate) -> Result<(), reqwest::Error> {
-        let res = self.http.post(&self.endpoint("game_metadata"))
-            .json(&game)
+        self.register_game(&game).await?;
+
+        self.game = Some(game.game.to_string());
+        self.update_interval = game.deinitialize_timer_length_ms;
+
+        self.launch_heart_beat_task();
+
+        Ok(())
+    }
+
+    /// Registers or updates another game, e.g. one described by a layout file.
+    /// Unlike `setup` it doesn't change the game of this client and sends no heartbeats,
+    /// the engine keeps the game and shows its events whenever they are triggered.
+    pub async fn register_game(&self, game: &C2SGameCreate) -> Result<(), reqwest::Error> {
+        let res = self.http.post(self.endpoint("game_metadata"))
+            .json(game)
             .send()
             .await;
 
-        debug!("Setup response: {:?}", res);
+        debug!("Register game response: {:?}", res);
 
         if res.is_err() {
             let err = res.err().unwrap();
@@ -36,14 +79,10 @@ impl SSEngineAPI {
             return Err(err);
         }
 
-        self.game = Some(game.game.to_string());
-        self.update_interval = game.deinitialize_timer_length_ms;
-
-        self.launch_heart_beat_task();
-
         Ok(())
     }
 
+    /// Sends a heartbeat every `deinitialize_timer_length_ms`, 10 seconds by default. Called by `setup`.
     pub fn launch_heart_beat_task (&mut self) {
         let body = C2SHeartBeat {
             game: self.game.clone().unwrap()
@@ -75,10 +114,11 @@ impl SSEngineAPI {
         self.heat_beat_task = Some(task);
     }
 
+    /// Registers an event, it does nothing on the screen until handlers are bound to it.
     pub async fn new_event(&self, event: C2SGameEventCreate) -> Result<(), reqwest::Error> {
         debug!("Creating a new event: {:?}", serde_json::to_string(&event));
 
-        let res = self.http.post(&self.endpoint("register_game_event"))
+        let res = self.http.post(self.endpoint("register_game_event"))
             .json(&event)
             .send()
             .await;
@@ -94,7 +134,7 @@ impl SSEngineAPI {
         Ok(())
     }
 
-    pub async fn new_event_and_bind(&self, event: C2SGameEventCreate, handlers: Vec<ScreenHandler>) -> Result<(), reqwest::Error> {
+    pub async fn new_event_and_bind(&self, event: C2SGameEventCreate, handlers: Vec<Handler>) -> Result<(), reqwest::Error> {
         self.new_event(event.clone()).await?;
         self.bind_event(C2SGameEventBind {
             game: event.game,
@@ -108,10 +148,19 @@ impl SSEngineAPI {
         Ok(())
     }
 
+    /// Binds handlers to an event, replacing the ones bound before.
+    /// The problems found by `C2SGameEventBind::validate` are only logged as warnings and never fail the call,
+    /// the binding is sent anyway since the engine may still use the rest of it.
+    /// Call `C2SGameEventBind::validate` first to refuse bindings with problems.
     pub async fn bind_event(&self, binding: C2SGameEventBind) -> Result<(), reqwest::Error> {
         debug!("Binding event: {:?}", serde_json::to_string(&binding));
+        if let Err(problems) = binding.validate() {
+            for problem in problems {
+                warn!("Event {}: {}", binding.event, problem);
+            }
+        }
 
-        let res = self.http.post(&self.endpoint("bind_game_event"))
+        let res = self.http.post(self.endpoint("bind_game_event"))
             .json(&binding)
             .send()
             .await;
@@ -127,15 +176,21 @@ impl SSEngineAPI {
         Ok(())
     }
 
+    /// Sends a value and context frame to an event of the game.
     pub async fn trigger_event(&self, event: String, data: Option<EventData>) -> Result<(), reqwest::Error> {
+        self.trigger_game_event(self.game.clone().unwrap(), event, data).await
+    }
+
+    /// Sends a value and context frame to an event of another game, see `register_game`.
+    pub async fn trigger_game_event(&self, game: String, event: String, data: Option<EventData>) -> Result<(), reqwest::Error> {
         let body = &C2STriggerEvent {
-            game: self.game.clone().unwrap(),
+            game,
             event,
             data
         };
 
         debug!("Triggering event {:?} ", serde_json::to_string(&body));
-        let res = self.http.post(&self.endpoint("game_event"))
+        let res = self.http.post(self.endpoint("game_event"))
             .json(body)
             .send()
             .await;
@@ -152,7 +207,7 @@ impl SSEngineAPI {
     }
 
     pub async fn remove_event(&self, event: C2SGameEventRemove) -> Result<(), reqwest::Error> {
-        let res = self.http.post(&self.endpoint("remove_game_event"))
+        let res = self.http.post(self.endpoint("remove_game_event"))
             .json(&event)
             .send()
             .await;
@@ -168,10 +223,48 @@ impl SSEngineAPI {
         Ok(())
     }
 
+    /// Uploads handlers written in GoLisp for the events of the game, instead of binding JSON handlers.
+    /// They replace the ones loaded before, see `arg::Arg` for expressions.
+    pub async fn load_golisp_handlers(&self, golisp: String) -> Result<(), reqwest::Error> {
+        let res = self.http.post(self.endpoint("load_golisp_handlers"))
+            .json(&C2SGoLispHandlers {
+                game: self.game.clone().unwrap(),
+                golisp
+            })
+            .send()
+            .await;
+
+        debug!("Load GoLisp handlers response: {:?}", res);
+
+        if res.is_err() {
+            let err = res.err().unwrap();
+            debug!("Error: {:?}", err);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Sends a payload to an event, usually bound with `payload::bind`.
+    pub async fn trigger_payload<P: EventPayload>(&self, event: String, payload: &P) -> Result<(), reqwest::Error> {
+        self.trigger_event(event, Some(payload.to_event_data())).await
+    }
+
+    /// Registers an event that lights the whole keyboard, see `trigger_bitmap`.
+    pub async fn new_bitmap_event(&self, event: C2SGameEventCreate) -> Result<(), reqwest::Error> {
+        self.new_event_and_bind(event, vec![bitmap::bitmap_handler().into()]).await
+    }
+
+    /// Shows a frame of an event registered with `new_bitmap_event`.
+    pub async fn trigger_bitmap(&self, event: String, bitmap: &KeyboardBitmap) -> Result<(), reqwest::Error> {
+        self.trigger_event(event, Some(bitmap.to_event_data())).await
+    }
+
+    /// Stops the heartbeats and removes the game from the engine.
     pub async fn done(&self) -> Result<(), reqwest::Error> {
         self.heat_beat_task.as_ref().unwrap().abort();
 
-        let res = self.http.post(&self.endpoint("remove_game"))
+        let res = self.http.post(self.endpoint("remove_game"))
             .json(&C2SGameRemove {
                 game: self.game.clone().unwrap()
             })
@@ -189,24 +282,22 @@ impl SSEngineAPI {
         Ok(())
     }
 
+    /// A client for the engine at `server`, e.g. `127.0.0.1:51999`.
+    /// When not given, the address is read from the `coreProps.json` of the engine,
+    /// or from the file `SSE_CORE_PROPS` points to.
     pub fn new(server: Option<&str>) -> SSEngineAPI {
-        let server_url = if server.is_none() {
-            let mut path = SSEngineAPI::DEFAULT_CORE_PROP_LOCATION.to_string();
-            #[cfg(target_os = "windows")] {
-                    let app_data = std::env::var("PROGRAMDATA").expect("No PROGRAMDATA directory");
-                    path = path.replace("%PROGRAMDATA%", &app_data);
-            }
-            let core_props = std::fs::read_to_string(path).unwrap();
+        let server_url = if let Some(server) = server {
+            server.to_string()
+        } else {
+            let core_props = std::fs::read_to_string(SSEngineAPI::core_props_path()).unwrap();
             let core_props: CoreProperties = serde_json::from_str(&core_props).unwrap();
             debug!("Core props: {:?}", core_props);
             debug!("Readed api url is: {}", core_props.address);
 
             core_props.address
-        } else {
-            server.unwrap().to_string()
         };
 
-        return SSEngineAPI {
+        SSEngineAPI {
             server: server_url,
             http: reqwest::Client::new(),
             game: None,
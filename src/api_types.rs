@@ -1,8 +1,12 @@
+//! Bodies of the requests to the engine, named after their direction: client to server.
+
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
-use crate::types::{Icon, ScreenHandler};
+use crate::bitmap::KeyboardBitmap;
+use crate::types::{Handler, Icon};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Registers a game, sent by `SSEngineAPI::setup` and `SSEngineAPI::register_game`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct C2SGameCreate {
     pub game: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -18,6 +22,7 @@ pub struct C2SHeartBeat {
     pub game: String
 }
 
+/// Sends event data, sent by `SSEngineAPI::trigger_event`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct C2STriggerEvent {
     pub game: String,
@@ -25,18 +30,133 @@ pub struct C2STriggerEvent {
     pub data: Option<EventData>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The value of an event, and the context frame that handlers read with `context-frame-key`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct EventData {
     pub value: EventValue,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub frame: Option<HashMap<String, EventValue>>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl EventData {
+    pub fn new(value: impl Into<EventValue>) -> EventData {
+        EventData {
+            value: value.into(),
+            frame: None
+        }
+    }
+
+    /// Adds a value to the context frame.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<EventValue>) -> EventData {
+        self.frame.get_or_insert_with(HashMap::new).insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds the picture shown by a screen handler without `image-data` to the frame,
+    /// under the key of the screen size that matches its length, e.g. `image-data-128x40` for 640 bytes.
+    pub fn with_image(self, image: &[u8]) -> Result<EventData, String> {
+        let key = image_key(image.len())
+            .ok_or(format!("No screen takes {} bytes of image data, expected 576, 640, 768 or 832", image.len()))?;
+        Ok(self.with(key, image.to_vec()))
+    }
+}
+
+/// Frame key of the image data for a screen, by the length of the data.
+fn image_key(length: usize) -> Option<&'static str> {
+    match length {
+        576 => Some("image-data-128x36"),
+        640 => Some("image-data-128x40"),
+        768 => Some("image-data-128x48"),
+        832 => Some("image-data-128x52"),
+        _ => None
+    }
+}
+
+/// A value in the event data. The fields of objects and the items of arrays can be read by `arg` accessors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum EventValue {
+    Bool(bool),
+    Number(i32),
+    /// Numbers with a fraction or out of the range of `Number`
+    Float(f64),
     String(String),
-    Number(i32)
+    /// The colors of all keys, see `bitmap::KeyboardBitmap::to_event_data`
+    Bitmap(Box<KeyboardBitmap>),
+    Array(Vec<EventValue>),
+    Object(HashMap<String, EventValue>)
+}
+
+impl From<bool> for EventValue {
+    fn from(value: bool) -> Self {
+        EventValue::Bool(value)
+    }
+}
+
+impl From<i32> for EventValue {
+    fn from(value: i32) -> Self {
+        EventValue::Number(value)
+    }
+}
+
+impl From<u8> for EventValue {
+    fn from(value: u8) -> Self {
+        EventValue::Number(value.into())
+    }
+}
+
+impl From<u16> for EventValue {
+    fn from(value: u16) -> Self {
+        EventValue::Number(value.into())
+    }
+}
+
+impl From<u32> for EventValue {
+    fn from(value: u32) -> Self {
+        i32::try_from(value).map_or(EventValue::Float(value.into()), EventValue::Number)
+    }
+}
+
+impl From<f32> for EventValue {
+    fn from(value: f32) -> Self {
+        EventValue::Float(value.into())
+    }
+}
+
+impl From<f64> for EventValue {
+    fn from(value: f64) -> Self {
+        EventValue::Float(value)
+    }
+}
+
+impl From<&str> for EventValue {
+    fn from(value: &str) -> Self {
+        EventValue::String(value.to_string())
+    }
+}
+
+impl From<String> for EventValue {
+    fn from(value: String) -> Self {
+        EventValue::String(value)
+    }
+}
+
+impl From<KeyboardBitmap> for EventValue {
+    fn from(value: KeyboardBitmap) -> Self {
+        EventValue::Bitmap(Box::new(value))
+    }
+}
+
+impl<T: Into<EventValue>> From<Vec<T>> for EventValue {
+    fn from(value: Vec<T>) -> Self {
+        EventValue::Array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<EventValue>> From<HashMap<String, T>> for EventValue {
+    fn from(value: HashMap<String, T>) -> Self {
+        EventValue::Object(value.into_iter().map(|(key, value)| (key, value.into())).collect())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -45,6 +165,7 @@ pub struct C2SGameRemove {
 }
 
 
+/// Registers an event of a game.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct C2SGameEventCreate {
     pub game: String,
@@ -55,6 +176,7 @@ pub struct C2SGameEventCreate {
     pub value_optional: bool
 }
 
+/// Binds handlers to an event.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct C2SGameEventBind {
     pub game: String,
@@ -62,7 +184,25 @@ pub struct C2SGameEventBind {
     pub min_value: i32,
     pub max_value: i32,
     pub icon_id: Icon,
-    pub handlers: Vec<ScreenHandler>,
+    pub handlers: Vec<Handler>,
+}
+
+impl C2SGameEventBind {
+    /// Checks the handlers for what the engine would reject or ignore, and reports all problems at once,
+    /// each with its path, e.g. `handlers[0].datas[1].repeats: only the last frame repeats`.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = vec![];
+        if self.min_value > self.max_value {
+            problems.push(format!("min_value: {} is greater than max_value {}", self.min_value, self.max_value));
+        }
+
+        for (i, handler) in self.handlers.iter().enumerate() {
+            problems.extend(handler.problems(self.min_value, self.max_value).into_iter()
+                .map(|problem| format!("handlers[{}].{}", i, problem)));
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -71,3 +211,10 @@ pub struct C2SGameEventRemove {
     pub event: String,
 }
 
+/// Handlers written in GoLisp, sent by `SSEngineAPI::load_golisp_handlers`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct C2SGoLispHandlers {
+    pub game: String,
+    pub golisp: String
+}
+
@@ -0,0 +1,228 @@
+//! Expressions of the engine's GoLisp for the `arg` of data accessors, and for handlers uploaded
+//! with `SSEngineAPI::load_golisp_handlers`. The data of the event is `self` in the expression.
+//!
+//! ```
+//! use sse_oled_controller::arg::Arg;
+//! use sse_oled_controller::screen::text;
+//!
+//! // "Hot" above 80 degrees, the temperature otherwise
+//! let temperature = Arg::frame_key("cpu").get("temperature");
+//! let line = text().arg(Arg::if_else(temperature.clone().gt(80), "Hot", temperature.to_text()));
+//! assert_eq!(Arg::frame_key("cpu").to_string(), "(cpu: (context-frame: self))");
+//! ```
+
+use std::fmt::{Display, Formatter};
+
+/// A GoLisp expression, built from the event data and literals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arg(String);
+
+impl Arg {
+    /// The value of the event.
+    pub fn value() -> Arg {
+        Arg("(value: self)".to_string())
+    }
+
+    /// The context frame of the event.
+    pub fn frame() -> Arg {
+        Arg("(context-frame: self)".to_string())
+    }
+
+    /// A key of the context frame, like `context-frame-key`. Nested objects are read with `get`.
+    pub fn frame_key(key: &str) -> Arg {
+        Arg::frame().get(key)
+    }
+
+    /// A field of an object, the key must be a valid symbol, e.g. `health` or `max-health`.
+    pub fn get(self, key: &str) -> Arg {
+        Arg(format!("({}: {})", key, self.0))
+    }
+
+    /// Any expression, copied as is.
+    pub fn raw(code: impl Into<String>) -> Arg {
+        Arg(code.into())
+    }
+
+    fn call(function: &str, args: impl IntoIterator<Item = Arg>) -> Arg {
+        let args: Vec<String> = args.into_iter().map(|arg| arg.0).collect();
+        Arg(format!("({} {})", function, args.join(" ")))
+    }
+
+    /// The number as text, e.g. to join it with `concat`.
+    pub fn to_text(self) -> Arg {
+        Arg::call("number->string", [self])
+    }
+
+    /// Joins texts and numbers into one text.
+    pub fn concat(parts: impl IntoIterator<Item = impl Into<Arg>>) -> Arg {
+        Arg::call("str", parts.into_iter().map(Into::into))
+    }
+
+    pub fn round(self) -> Arg {
+        Arg::call("round", [self])
+    }
+
+    pub fn floor(self) -> Arg {
+        Arg::call("floor", [self])
+    }
+
+    pub fn plus(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("+", [self, other.into()])
+    }
+
+    pub fn minus(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("-", [self, other.into()])
+    }
+
+    pub fn times(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("*", [self, other.into()])
+    }
+
+    pub fn divided_by(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("/", [self, other.into()])
+    }
+
+    /// The number in percent of `max`, rounded.
+    pub fn percent_of(self, max: impl Into<Arg>) -> Arg {
+        self.times(100).divided_by(max).round()
+    }
+
+    pub fn eq(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("==", [self, other.into()])
+    }
+
+    pub fn gt(self, other: impl Into<Arg>) -> Arg {
+        Arg::call(">", [self, other.into()])
+    }
+
+    pub fn lt(self, other: impl Into<Arg>) -> Arg {
+        Arg::call("<", [self, other.into()])
+    }
+
+    pub fn and(self, other: Arg) -> Arg {
+        Arg::call("and", [self, other])
+    }
+
+    pub fn or(self, other: Arg) -> Arg {
+        Arg::call("or", [self, other])
+    }
+
+    /// `then` when the condition holds, `otherwise` when it doesn't.
+    pub fn if_else(condition: Arg, then: impl Into<Arg>, otherwise: impl Into<Arg>) -> Arg {
+        Arg::call("if", [condition, then.into(), otherwise.into()])
+    }
+}
+
+impl std::ops::Not for Arg {
+    type Output = Arg;
+
+    fn not(self) -> Arg {
+        Arg::call("not", [self])
+    }
+}
+
+impl Display for Arg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Arg> for String {
+    fn from(arg: Arg) -> Self {
+        arg.0
+    }
+}
+
+impl From<i32> for Arg {
+    fn from(value: i32) -> Self {
+        Arg(value.to_string())
+    }
+}
+
+/// Fails for NaN and infinity, GoLisp has no literal for them.
+impl TryFrom<f64> for Arg {
+    type Error = String;
+
+    fn try_from(value: f64) -> Result<Self, String> {
+        if !value.is_finite() {
+            return Err(format!("{} has no GoLisp literal", value));
+        }
+
+        // Keep a decimal point so that the engine doesn't divide integers
+        Ok(Arg(format!("{:?}", value)))
+    }
+}
+
+impl From<bool> for Arg {
+    fn from(value: bool) -> Self {
+        Arg(if value { "#t" } else { "#f" }.to_string())
+    }
+}
+
+/// A text literal.
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::from(value.as_str())
+    }
+}
+
+/// Keys of the context frame that GoLisp `code` reads the way `Arg::frame_key` writes them, `(key: (context-frame: self))`.
+/// Keys that are computed, or read from a frame bound to a variable, aren't found.
+pub fn frame_keys(code: &str) -> Vec<&str> {
+    code.match_indices("(context-frame: self)")
+        .filter_map(|(at, _)| {
+            let before = code[..at].strip_suffix(": ")?;
+            let key = &before[before.rfind('(')? + 1..];
+            (!key.is_empty() && !key.contains(char::is_whitespace)).then_some(key)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_else() {
+        let arg = Arg::if_else(Arg::value().gt(80), "Hot", Arg::value().to_text());
+        assert_eq!(arg.to_string(), r#"(if (> (value: self) 80) "Hot" (number->string (value: self)))"#);
+    }
+
+    #[test]
+    fn concat() {
+        let arg = Arg::concat([Arg::from("CPU "), Arg::frame_key("cpu").to_text(), Arg::from("%")]);
+        assert_eq!(arg.to_string(), r#"(str "CPU " (number->string (cpu: (context-frame: self))) "%")"#);
+    }
+
+    #[test]
+    fn percent_of() {
+        let arg = Arg::frame_key("used").percent_of(Arg::frame_key("total"));
+        assert_eq!(arg.to_string(), "(round (/ (* (used: (context-frame: self)) 100) (total: (context-frame: self))))");
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(Arg::from(r#"say "hi" \ bye"#).to_string(), r#""say \"hi\" \\ bye""#);
+    }
+
+    #[test]
+    fn floats_keep_a_decimal_point() {
+        assert_eq!(Arg::try_from(2.0).unwrap().to_string(), "2.0");
+        assert!(Arg::try_from(f64::NAN).is_err());
+        assert!(Arg::try_from(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn finds_frame_keys() {
+        let arg = Arg::if_else(Arg::frame_key("cpu").get("temperature").gt(80), Arg::frame_key("hot"), Arg::value().to_text());
+        assert_eq!(frame_keys(&arg.to_string()), ["cpu", "hot"]);
+        assert_eq!(frame_keys(&Arg::frame().to_string()), Vec::<&str>::new());
+        assert_eq!(frame_keys("(let (f (context-frame: self)) (cpu: f))"), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use chrono::Utc;
+use chrono_tz::Tz;
+use tokio::sync::Mutex;
+use sse_oled_controller::api_types::{C2SGameEventCreate, EventData, EventValue};
+use sse_oled_controller::payload::EventPayload;
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::screen::{text, Screen};
+use sse_oled_controller::types::{Icon, ScreenHandler};
+use crate::config::{ClockConfig, ZoneConfig};
+use crate::controller::Controller;
+
+pub const GAME: &str = "OLED_CLOCK";
+/// Name of the clock's screen provider
+pub const PROVIDER: &str = "clock";
+pub const TIME_UPDATE: &str = "TIME_UPDATE";
+
+pub fn time_update_event() -> C2SGameEventCreate {
+    C2SGameEventCreate {
+        game: GAME.to_string(),
+        event: TIME_UPDATE.to_string(),
+        min_value: 0,
+        max_value: 1,
+        icon_id: Icon::Timer,
+        value_optional: false,
+    }
+}
+
+/// Builds the `TIME_UPDATE` screen from the config.
+/// The time itself is the event value, everything else is read from the context frame.
+pub fn time_update_handler(config: &ClockConfig) -> ScreenHandler {
+    let first_line = if config.zones.is_empty() {
+        text().prefix(&config.game_prefix).frame_key("curr_game")
+    } else {
+        text().prefix(&config.zone_prefix).frame_key("zone")
+    };
+
+    let mut frame = Screen::multi_line()
+        .line(first_line)
+        .line(text().prefix(&config.time_prefix));
+
+    if config.date_format.is_some() {
+        frame = frame.line(text().prefix(&config.date_prefix).frame_key("date"));
+    }
+
+    if config.footer.is_some() || config.status_key.is_some() {
+        frame = frame.line(text().frame_key("footer"));
+    }
+
+    Screen::new().frame(frame).build()
+}
+
+/// Formats the current time in the given timezone, or the system timezone when `None`.
+pub fn format_now(tz: Option<Tz>, format: &str) -> String {
+    match tz {
+        Some(tz) => Utc::now().with_timezone(&tz).format(format).to_string(),
+        None => chrono::Local::now().format(format).to_string()
+    }
+}
+
+/// Picks the world clock to show after the clock has been running for `elapsed`.
+pub fn zone_at(config: &ClockConfig, elapsed: Duration) -> Option<&ZoneConfig> {
+    if config.zones.is_empty() {
+        return None;
+    }
+
+    let slot = elapsed.as_secs() / config.zone_rotate_secs;
+    config.zones.get(slot as usize % config.zones.len())
+}
+
+/// Data of `TIME_UPDATE`.
+#[derive(EventPayload)]
+struct TimeUpdate {
+    #[payload(value)]
+    time: String,
+    curr_game: String,
+    /// Label of the world clock
+    zone: Option<String>,
+    date: Option<String>,
+    /// The status from the context while it's set, the footer otherwise
+    footer: EventValue
+}
+
+/// The last line shows the status from `context` while it's set, the footer otherwise.
+pub fn time_update_data(config: &ClockConfig, game_name: &str, zone: Option<&ZoneConfig>,
+                        context: &HashMap<String, EventValue>) -> EventData {
+    let tz = match zone {
+        Some(zone) => zone.tz().ok(),
+        None => config.tz().ok().flatten()
+    };
+
+    let status = config.status_key.as_ref().and_then(|key| context.get(key));
+    let footer = config.footer.clone().unwrap_or_default();
+
+    let mut data = TimeUpdate {
+        time: format_now(tz, config.time_format()),
+        curr_game: game_name.to_string(),
+        zone: zone.map(|zone| zone.label.clone()),
+        date: config.date_format.as_ref().map(|date_format| format_now(tz, date_format)),
+        footer: status.cloned().unwrap_or(EventValue::String(footer))
+    }.to_event_data();
+
+    // The other values are there for GoLisp handlers of `TIME_UPDATE`
+    let frame = data.frame.get_or_insert_with(HashMap::new);
+    for (key, value) in context {
+        frame.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+
+    data
+}
+
+/// Shows the time, with the game name and the context values of the controller.
+/// Always ready, so it's on the screen whenever nothing else is.
+pub struct ClockProvider {
+    config: Arc<Mutex<ClockConfig>>,
+    controller: Arc<Controller>,
+    started: Instant,
+    /// Label of the world clock to keep on the screen instead of rotating
+    pinned: Option<String>
+}
+
+impl ClockProvider {
+    pub fn new(config: Arc<Mutex<ClockConfig>>, controller: Arc<Controller>) -> ClockProvider {
+        ClockProvider {
+            config,
+            controller,
+            started: Instant::now(),
+            pinned: None
+        }
+    }
+}
+
+#[async_trait]
+impl ScreenProvider for ClockProvider {
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        Ok(vec![EventBinding::for_payload::<TimeUpdate>(time_update_event(),
+            vec![time_update_handler(&*self.config.lock().await).into()])?])
+    }
+
+    async fn is_ready(&mut self, _now: Instant) -> bool {
+        true
+    }
+
+    async fn frame(&mut self, _now: Instant, _fresh: bool) -> Result<Frame, reqwest::Error> {
+        let config = self.config.lock().await;
+        let pinned = self.pinned.as_ref().and_then(|label| config.zones.iter().find(|zone| zone.label == *label));
+        let zone = pinned.or_else(|| zone_at(&config, self.started.elapsed()));
+        let data = time_update_data(&config, &self.controller.game_name.lock().await, zone,
+            &*self.controller.context.lock().await);
+
+        Ok(Frame::Event(TIME_UPDATE.to_string(), data))
+    }
+
+    /// `zone <label>` keeps one world clock on the screen, `zone rotate` shows them in turn again.
+    async fn command(&mut self, args: Vec<String>) -> Result<String, String> {
+        let Some(("zone", label)) = args.split_first().map(|(first, rest)| (first.as_str(), rest.join(" "))) else {
+            return Err("Usage: zone <label> | zone rotate".to_string());
+        };
+
+        if label == "rotate" {
+            self.pinned = None;
+            return Ok("World clocks rotate again".to_string());
+        }
+
+        let config = self.config.lock().await;
+        if !config.zones.iter().any(|zone| zone.label == label) {
+            let labels: Vec<&str> = config.zones.iter().map(|zone| zone.label.as_str()).collect();
+            return Err(format!("Unknown zone: {}, expected one of {}", label, labels.join(", ")));
+        }
+
+        self.pinned = Some(label.clone());
+        Ok(format!("Showing {}", label))
+    }
+}
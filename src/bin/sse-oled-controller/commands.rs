@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use log::info;
+use std::time::Instant;
+use sse_oled_controller::notify::{DEFAULT_NOTIFY_DURATION, DEFAULT_NOTIFY_ICON};
+use crate::controller::Controller;
+use crate::notifications::{Notification, DEFAULT_NOTIFY_EXPIRY};
+use crate::repl::{ArgKind, ArgSpec, Command, Commands, Flow};
+use crate::{clock, timers};
+
+/// Commands run on the REPL thread, so they may block on the locks of the controller.
+pub struct ReplContext {
+    pub controller: Arc<Controller>
+}
+
+/// `manual` names win over the detected game until they time out.
+fn set_game_name(context: &ReplContext, name: &str, manual: bool) {
+    let mut manual_since = context.controller.manual_game_since.blocking_lock();
+    let mut game_name = context.controller.game_name.blocking_lock();
+    *game_name = name.to_string();
+    *manual_since = manual.then(Instant::now);
+    context.controller.screen.refresh(clock::PROVIDER);
+    info!("Update game name to {}", *game_name);
+}
+
+fn cancel_timer(label: &'static str) -> Command<ReplContext> {
+    Command::new("cancel", "Cancel the running timer")
+        .handler(move |context: &ReplContext, _| {
+            context.controller.timers.blocking_lock().cancel();
+            context.controller.screen.wake();
+            info!("Cancelled {}", label);
+            Ok(Flow::Continue)
+        })
+}
+
+fn start_timer(context: &ReplContext, label: &str, duration: std::time::Duration) -> Result<Flow, String> {
+    context.controller.timers.blocking_lock().start_timer(label, duration);
+    context.controller.screen.wake();
+    info!("{} set for {}", label, timers::format_duration(duration));
+    Ok(Flow::Continue)
+}
+
+/// Sends the rest of the line to the provider.
+fn provider_command(name: &'static str) -> Command<ReplContext> {
+    Command::new(name, "Send a command to this provider")
+        .arg(ArgSpec::required("command", ArgKind::Words))
+        .handler(move |context: &ReplContext, args| {
+            let words = args.words("command").unwrap_or_default().to_vec();
+            info!("{}", context.controller.screen.blocking_command(name, words)?);
+            Ok(Flow::Continue)
+        })
+}
+
+/// `providers` are the names of the screen providers, each gets a `screen` subcommand.
+pub fn commands(providers: &[&'static str]) -> Commands<ReplContext> {
+    let mut commands = Commands::new();
+
+    commands
+        .add(Command::new("set", "Set the game name")
+            .help("Sets the game name. When games are detected, it wins over the detected game \
+                until game_override_secs have passed or 'idle' is used.")
+            .arg(ArgSpec::required("game name", ArgKind::Rest))
+            .handler(|context, args| {
+                set_game_name(context, args.text("game name").unwrap_or_default(), true);
+                Ok(Flow::Continue)
+            }))
+        .add(Command::new("idle", "Set the game name to IDLE")
+            .help("Sets the game name to IDLE. When games are detected, the detected game is shown again on the next scan.")
+            .handler(|context, _| {
+                set_game_name(context, "IDLE", false);
+                Ok(Flow::Continue)
+            }))
+        .add(Command::new("timer", "Start a timer with a progress bar")
+            .help("Starts a timer, e.g. 'timer 25m' or 'timer 1h30m'. The screen blinks when it ends.")
+            .arg(ArgSpec::required("duration", ArgKind::Duration))
+            .subcommand(cancel_timer("timer"))
+            .handler(|context, args| {
+                start_timer(context, "Timer", args.duration("duration").unwrap_or_default())
+            }))
+        .add(Command::new("countdown", "Count down to a time of day")
+            .help("Counts down to the next HH:MM[:SS] or to a YYYY-MM-DD HH:MM in local time. The screen blinks when it ends.")
+            .arg(ArgSpec::required("until", ArgKind::Until))
+            .subcommand(cancel_timer("countdown"))
+            .handler(|context, args| {
+                start_timer(context, "Countdown", args.duration("until").unwrap_or_default())
+            }))
+        .add(Command::new("stopwatch", "Control the stopwatch")
+            .help("'start' starts or resumes the stopwatch, 'stop' pauses it, 'lap' records a lap \
+                and 'reset' clears it and goes back to the clock.")
+            .arg(ArgSpec::required("action", ArgKind::Choice(&["start", "stop", "lap", "reset"])))
+            .handler(|context: &ReplContext, args| {
+                let message = context.controller.timers.blocking_lock().stopwatch(args.text("action").unwrap_or_default())?;
+                context.controller.screen.wake();
+                info!("{}", message);
+                Ok(Flow::Continue)
+            }))
+        .add(Command::new("notify", "Show a message for a while, then go back to the clock")
+            .arg(ArgSpec::required("text", ArgKind::Rest))
+            .handler(|context: &ReplContext, args| {
+                let controller = &context.controller;
+                controller.notifications.blocking_lock().push(Notification {
+                    text: args.text("text").unwrap_or_default().to_string(),
+                    icon: DEFAULT_NOTIFY_ICON,
+                    duration: DEFAULT_NOTIFY_DURATION,
+                    priority: 0,
+                    blinks: None,
+                    expires_at: Some(Instant::now() + DEFAULT_NOTIFY_EXPIRY)
+                });
+                controller.screen.wake();
+                Ok(Flow::Continue)
+            }))
+        .add(providers.iter().fold(Command::new("screen", "Show which provider is on the screen")
+            .help("Lists the screen providers by priority. The ready one with the highest priority is shown, \
+                providers with the same priority take turns. 'screen <provider> <command>' sends a command to \
+                a provider, e.g. 'screen clock zone Berlin' or 'screen notifications clear'."),
+                |command, name| command.subcommand(provider_command(name)))
+            .handler(|context: &ReplContext, _| {
+                let screen = context.controller.screen.status.blocking_lock();
+                info!("On the screen: {}", screen.active.as_deref().unwrap_or("nothing"));
+                let mut providers = screen.providers.clone();
+                providers.sort_by_key(|provider| std::cmp::Reverse(provider.priority));
+                for provider in providers {
+                    info!("  {} - priority {}, refresh {}ms, dwell {}s{}", provider.name, provider.priority,
+                        provider.refresh.as_millis(), provider.min_dwell.as_secs(), if provider.ready { ", ready" } else { "" });
+                }
+                Ok(Flow::Continue)
+            }))
+        .add(Command::new("exit", "Exit the program")
+            .handler(|_, _| Ok(Flow::Exit)));
+
+    commands
+}
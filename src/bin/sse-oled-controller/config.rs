@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sse_oled_controller::control::DEFAULT_CONTROL_ADDRESS;
+use sse_oled_controller::scheduler::ScreenConfig;
+use crate::metrics::Metric;
+
+/// Settings of the clock screen, read from a JSON file and overridden by command line flags.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// The file this config was loaded from
+    #[serde(skip)]
+    pub source: Option<String>,
+    /// Address of the engine, read from coreProps.json when not set
+    pub server: Option<String>,
+    /// strftime format of the time line, takes precedence over `hour12`
+    pub time_format: Option<String>,
+    pub hour12: bool,
+    /// IANA timezone name, e.g. "Asia/Shanghai". System timezone is used when not set
+    pub timezone: Option<String>,
+    /// strftime format of the date line, the line is hidden when not set
+    pub date_format: Option<String>,
+    pub game_prefix: String,
+    pub time_prefix: String,
+    pub date_prefix: String,
+    /// Text of the last line, the line is hidden when not set
+    pub footer: Option<String>,
+    /// Context key shown on the last line instead of the footer while it's set, e.g. with `set_context`
+    pub status_key: Option<String>,
+    /// World clocks shown in turn, the label replaces the game name on the first line
+    pub zones: Vec<ZoneConfig>,
+    pub zone_prefix: String,
+    /// Seconds each zone stays on the screen
+    pub zone_rotate_secs: u64,
+    /// Layout files with extra events to register at startup
+    pub layouts: Vec<String>,
+    /// Files of handlers written in GoLisp, uploaded together after the layouts
+    pub golisp: Vec<String>,
+    /// Address of the local control endpoint, disabled when not set
+    pub control: Option<String>,
+    /// Address of the voice chat socket that takes JSON lines, disabled when not set
+    pub voice: Option<String>,
+    /// Scheduling of the screen providers by name, e.g. `clock`. Only read at startup
+    pub screens: HashMap<String, ScreenConfig>,
+    /// Executable names mapped to the name shown as the game, e.g. "eldenring.exe": "Elden Ring".
+    /// The running processes are only watched when this is not empty
+    pub games: HashMap<String, String>,
+    /// Seconds between two scans of the running processes
+    pub game_scan_secs: u64,
+    /// Seconds a name given with `set` wins over the detected game
+    pub game_override_secs: u64,
+    /// System metrics shown in turn with the clock. Only read at startup
+    pub metrics: HashMap<Metric, MetricConfig>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MetricConfig {
+    /// Milliseconds between two samples, also while the metric isn't on the screen
+    pub refresh_millis: u64,
+    /// Show an alert when the value goes above this: percent for cpu and memory, °C, or KiB/s for network
+    pub alert_above: Option<f64>,
+    /// hwmon sensor of the temperature, e.g. "k10temp", or interface of the network, e.g. "eth0".
+    /// The hottest sensor and all interfaces but loopback by default
+    pub source: Option<String>,
+    /// Value of a full progress bar, 100 °C for temperature and 12500 KiB/s for network by default
+    pub max: Option<f64>
+}
+
+impl Default for MetricConfig {
+    fn default() -> Self {
+        MetricConfig {
+            refresh_millis: 2000,
+            alert_above: None,
+            source: None,
+            max: None
+        }
+    }
+}
+
+/// Flags that aren't settings of the config.
+#[derive(Default)]
+struct Flags {
+    config: Option<String>,
+    help: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ZoneConfig {
+    pub label: String,
+    /// IANA timezone name
+    pub timezone: String
+}
+
+impl ZoneConfig {
+    pub fn tz(&self) -> Result<Tz, String> {
+        parse_tz(&self.timezone)
+    }
+}
+
+fn parse_tz(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>().map_err(|_| format!("Unknown timezone: {}", name))
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            source: None,
+            server: None,
+            time_format: None,
+            hour12: false,
+            timezone: None,
+            date_format: None,
+            game_prefix: "Now  ".to_string(),
+            time_prefix: "Time  ".to_string(),
+            date_prefix: "Date  ".to_string(),
+            footer: Some("/ lamadaemon /".to_string()),
+            status_key: Some("status".to_string()),
+            zones: vec![],
+            zone_prefix: "".to_string(),
+            zone_rotate_secs: 5,
+            layouts: vec![],
+            golisp: vec![],
+            control: Some(DEFAULT_CONTROL_ADDRESS.to_string()),
+            voice: None,
+            screens: HashMap::new(),
+            games: HashMap::new(),
+            game_scan_secs: 5,
+            game_override_secs: 1800,
+            metrics: HashMap::new()
+        }
+    }
+}
+
+impl ClockConfig {
+    pub const USAGE: &'static str = "Usage: sse-oled-controller [options]
+  --config <file>         Load settings from a JSON or .toml file
+  --server <address>      Engine address, e.g. 127.0.0.1:51234
+  --format <strftime>     Format of the time line
+  --12h / --24h           Use 12 or 24 hour clock
+  --timezone <name>       IANA timezone, e.g. Europe/Berlin
+  --date <strftime>       Show a date line with the given format
+  --no-date               Hide the date line
+  --game-prefix <text>    Prefix of the game line
+  --time-prefix <text>    Prefix of the time line
+  --date-prefix <text>    Prefix of the date line
+  --footer <text>         Text of the last line
+  --no-footer             Hide the last line
+  --status-key <key>      Context key shown instead of the footer while set, status by default
+  --no-status-key         Always show the footer
+  --zone <label>=<name>   Add a world clock, can be repeated
+  --zone-prefix <text>    Prefix of the zone label
+  --rotate <seconds>      How long each world clock is shown
+  --layout <file>         Register the events of a layout file, can be repeated
+  --golisp <file>         Upload the GoLisp handlers of a file, can be repeated
+  --control <address>     Address of the local control endpoint, 127.0.0.1:51290 by default
+  --no-control            Disable the control endpoint
+  --voice <address>       Listen for voice chat status as JSON lines, e.g. 127.0.0.1:51291
+  --game <exe>=<name>     Show <name> as the game while <exe> runs, can be repeated
+  --metric <name>         Show cpu, memory, temperature or network in turn with the clock, can be repeated
+  --help                  Show this message";
+
+    /// Reads a `.toml` file as TOML, any other file as JSON.
+    pub fn load(path: impl AsRef<Path>) -> Result<ClockConfig, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml")) {
+            toml::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+        } else {
+            serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+        }
+    }
+
+    /// Builds the config from command line arguments (without the program name), `None` when `--help` was given.
+    /// The file of `--config` is loaded first so that the other flags always override it.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<ClockConfig>, String> {
+        let args: Vec<String> = args.into_iter().collect();
+
+        let mut config = ClockConfig::default();
+        let flags = config.apply_flags(&args)?;
+        if flags.help {
+            return Ok(None);
+        }
+
+        // The flags are applied again on top of the file
+        if let Some(path) = flags.config {
+            config = ClockConfig {
+                source: Some(path.clone()),
+                ..ClockConfig::load(&path)?
+            };
+            config.apply_flags(&args)?;
+        }
+
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    /// Applies the flags to the config, returns the ones that aren't settings.
+    fn apply_flags(&mut self, args: &[String]) -> Result<Flags, String> {
+        let mut flags = Flags::default();
+
+        let mut args = args.iter().cloned();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", flag));
+
+            match flag.as_str() {
+                "--config" => flags.config = Some(value()?),
+                "--server" => self.server = Some(value()?),
+                "--format" => self.time_format = Some(value()?),
+                "--12h" => self.hour12 = true,
+                "--24h" => self.hour12 = false,
+                "--timezone" => self.timezone = Some(value()?),
+                "--date" => self.date_format = Some(value()?),
+                "--no-date" => self.date_format = None,
+                "--game-prefix" => self.game_prefix = value()?,
+                "--time-prefix" => self.time_prefix = value()?,
+                "--date-prefix" => self.date_prefix = value()?,
+                "--footer" => self.footer = Some(value()?),
+                "--no-footer" => self.footer = None,
+                "--status-key" => self.status_key = Some(value()?),
+                "--no-status-key" => self.status_key = None,
+                "--zone" => {
+                    let zone = value()?;
+                    let (label, timezone) = zone.split_once('=')
+                        .ok_or(format!("Invalid zone {}, expected <label>=<timezone>", zone))?;
+                    self.zones.push(ZoneConfig {
+                        label: label.to_string(),
+                        timezone: timezone.to_string()
+                    });
+                },
+                "--zone-prefix" => self.zone_prefix = value()?,
+                "--rotate" => self.zone_rotate_secs = value()?.parse()
+                    .map_err(|_| "Invalid value for --rotate".to_string())?,
+                "--layout" => self.layouts.push(value()?),
+                "--golisp" => self.golisp.push(value()?),
+                "--control" => self.control = Some(value()?),
+                "--no-control" => self.control = None,
+                "--voice" => self.voice = Some(value()?),
+                "--game" => {
+                    let game = value()?;
+                    let (executable, name) = game.split_once('=')
+                        .ok_or(format!("Invalid game {}, expected <exe>=<name>", game))?;
+                    self.games.insert(executable.to_string(), name.to_string());
+                },
+                "--metric" => {
+                    let metric = value()?.parse()?;
+                    self.metrics.entry(metric).or_default();
+                },
+                "--help" => flags.help = true,
+                _ => return Err(format!("Unknown option: {}\n{}", flag, ClockConfig::USAGE))
+            }
+        }
+
+        Ok(flags)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.tz()?;
+        for zone in &self.zones {
+            zone.tz()?;
+        }
+
+        if let Some(control) = &self.control {
+            let address = control.parse::<SocketAddr>().map_err(|_| format!("Invalid control address: {}", control))?;
+            // Anyone who can reach the endpoint can drive the screen, it has no authentication
+            if !address.ip().is_loopback() {
+                return Err(format!("Control address {} is not a loopback address, the endpoint has no authentication", control));
+            }
+        }
+
+        if let Some(voice) = &self.voice {
+            let address = voice.parse::<SocketAddr>().map_err(|_| format!("Invalid voice address: {}", voice))?;
+            // Same as the control endpoint, whoever connects decides what the screen shows
+            if !address.ip().is_loopback() {
+                return Err(format!("Voice address {} is not a loopback address, the socket has no authentication", voice));
+            }
+        }
+
+        if self.zone_rotate_secs == 0 {
+            return Err("zone_rotate_secs must be greater than 0".to_string());
+        }
+
+        if self.game_scan_secs == 0 {
+            return Err("game_scan_secs must be greater than 0".to_string());
+        }
+
+        // The samplers read /proc and /sys
+        if cfg!(not(target_os = "linux")) && !self.metrics.is_empty() {
+            return Err("System metrics are only available on Linux".to_string());
+        }
+
+        for (metric, config) in &self.metrics {
+            if config.refresh_millis == 0 {
+                return Err(format!("refresh_millis of metric {} must be greater than 0", metric.name()));
+            }
+            if config.max.is_some_and(|max| !max.is_finite() || max <= 0.0) {
+                return Err(format!("max of metric {} must be a number greater than 0", metric.name()));
+            }
+        }
+
+        for (name, screen) in &self.screens {
+            if screen.refresh_millis == Some(0) {
+                return Err(format!("refresh_millis of screen {} must be greater than 0", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn tz(&self) -> Result<Option<Tz>, String> {
+        self.timezone.as_deref().map(parse_tz).transpose()
+    }
+
+    pub fn time_format(&self) -> &str {
+        match &self.time_format {
+            Some(format) => format,
+            None if self.hour12 => "%I:%M:%S %p",
+            None => "%H:%M:%S"
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::http::uri::Authority;
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, info};
+use tokio::sync::Mutex;
+use sse_oled_controller::api::SSEngineAPI;
+use sse_oled_controller::api_types::EventValue;
+use sse_oled_controller::control::{self, ControlRequest, ControlResponse, ControlStatus, DurationSpec, IconSpec};
+use sse_oled_controller::notify::{DEFAULT_NOTIFY_DURATION, DEFAULT_NOTIFY_ICON};
+use sse_oled_controller::scheduler::ScreenHandle;
+use crate::clock;
+use crate::notifications::{Notification, NotificationQueue, DEFAULT_NOTIFY_EXPIRY};
+use crate::timers::{self, Timers};
+
+/// State of the running clock, shared by the scheduler's providers, the REPL and the control endpoint.
+pub struct Controller {
+    pub api: Arc<SSEngineAPI>,
+    pub game_name: Mutex<String>,
+    /// When the game name was last set by hand, it wins over the detected game for a while.
+    /// Locked before `game_name` when both are needed, so that the game detection doesn't undo a `set`
+    pub manual_game_since: Mutex<Option<Instant>>,
+    pub timers: Mutex<Timers>,
+    /// Extra values merged into the context frame of the clock
+    pub context: Mutex<HashMap<String, EventValue>>,
+    pub notifications: Mutex<NotificationQueue>,
+    /// The scheduler, woken up when something should be shown right away
+    pub screen: Arc<ScreenHandle>
+}
+
+impl Controller {
+    pub fn new(api: Arc<SSEngineAPI>, screen: Arc<ScreenHandle>) -> Controller {
+        Controller {
+            api,
+            game_name: Mutex::new("IDLE".to_string()),
+            manual_game_since: Mutex::new(None),
+            timers: Mutex::new(Timers::new()),
+            context: Mutex::new(HashMap::new()),
+            notifications: Mutex::new(NotificationQueue::new()),
+            screen
+        }
+    }
+
+    /// Queues a notification, the scheduler shows it as soon as nothing more important is on the screen.
+    pub async fn notify(&self, notification: Notification) {
+        info!("Notify: {}", notification.text);
+        self.notifications.lock().await.push(notification);
+        self.screen.wake();
+    }
+
+    pub async fn status(&self) -> ControlStatus {
+        ControlStatus {
+            game: self.game_name.lock().await.clone(),
+            mode: match self.notifications.lock().await.showing(Instant::now()) {
+                Some(notification) => format!("notification {}", notification.text),
+                None => self.timers.lock().await.describe()
+            },
+            context: self.context.lock().await.clone(),
+            screen: self.screen.status.lock().await.active.clone()
+        }
+    }
+
+    pub async fn handle(&self, request: ControlRequest) -> ControlResponse {
+        debug!("Control request: {:?}", request);
+
+        match self.execute(request).await {
+            Ok(message) => ControlResponse {
+                message,
+                ..ControlResponse::ok(self.status().await)
+            },
+            Err(e) => ControlResponse::error(e)
+        }
+    }
+
+    /// Runs the request, returns the message of a provider command.
+    async fn execute(&self, request: ControlRequest) -> Result<Option<String>, String> {
+        match request {
+            ControlRequest::SetGame { name } => {
+                info!("Update game name to {}", name);
+                let mut manual_since = self.manual_game_since.lock().await;
+                *self.game_name.lock().await = name;
+                *manual_since = Some(Instant::now());
+                self.screen.refresh(clock::PROVIDER);
+            },
+            ControlRequest::Idle => {
+                info!("Update game name to IDLE");
+                let mut manual_since = self.manual_game_since.lock().await;
+                *self.game_name.lock().await = "IDLE".to_string();
+                *manual_since = None;
+                self.screen.refresh(clock::PROVIDER);
+            },
+            ControlRequest::SetContext { key, value } => {
+                let mut context = self.context.lock().await;
+                match value {
+                    Some(value) => { context.insert(key, value); },
+                    None => { context.remove(&key); }
+                }
+                self.screen.refresh(clock::PROVIDER);
+            },
+            ControlRequest::Trigger { game, event, data } => {
+                let result = match game {
+                    Some(game) => self.api.trigger_game_event(game, event, data).await,
+                    None => self.api.trigger_event(event, data).await
+                };
+                result.map_err(|e| e.to_string())?;
+            },
+            ControlRequest::Timer { duration } => {
+                let duration = control::parse_duration(&duration)?;
+                self.timers.lock().await.start_timer("Timer", duration);
+            },
+            ControlRequest::Countdown { until } => {
+                let duration = timers::parse_until(&until)?;
+                self.timers.lock().await.start_timer("Countdown", duration);
+            },
+            ControlRequest::Stopwatch { action } => {
+                self.timers.lock().await.stopwatch(&action)?;
+            },
+            ControlRequest::Notify { text, icon, duration, priority, blinks, expires } => {
+                let parse = |duration: Option<DurationSpec>, default: Duration| {
+                    duration.as_ref().map(DurationSpec::duration).transpose().map(|d| d.unwrap_or(default))
+                };
+
+                self.notify(Notification {
+                    text,
+                    icon: icon.as_ref().map(IconSpec::icon).transpose()?.unwrap_or(DEFAULT_NOTIFY_ICON),
+                    duration: parse(duration, DEFAULT_NOTIFY_DURATION)?,
+                    priority: priority.unwrap_or_default(),
+                    blinks,
+                    expires_at: Some(Instant::now().checked_add(parse(expires, DEFAULT_NOTIFY_EXPIRY)?)
+                        .ok_or("Invalid expiry")?)
+                }).await;
+            },
+            ControlRequest::Screen { provider, args } => {
+                return self.screen.command(&provider, args).await.map(Some);
+            },
+            ControlRequest::Clock => self.timers.lock().await.cancel(),
+            ControlRequest::Status => {}
+        }
+
+        // Let the scheduler pick up a started or cancelled timer right away
+        self.screen.wake();
+        Ok(None)
+    }
+}
+
+/// Refuses requests that a web page could have made, so that visiting one can't control the clock:
+/// the Host must be this machine on our port, which rules out DNS rebinding,
+/// and commands must be JSON, which browsers don't send cross origin without asking first.
+fn check_request(request: &Request<Body>, address: SocketAddr) -> Result<(), (StatusCode, String)> {
+    let host = request.headers().get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+        .ok_or((StatusCode::BAD_REQUEST, "Missing or invalid Host header".to_string()))?;
+
+    let name = host.host().trim_start_matches('[').trim_end_matches(']');
+    let loopback = name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    if !loopback || host.port_u16().unwrap_or(80) != address.port() {
+        return Err((StatusCode::FORBIDDEN, format!("Host {} is not this control endpoint", host)));
+    }
+
+    if request.method() == Method::POST {
+        let json = request.headers().get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(|content_type| content_type.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+        if !json {
+            return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Commands must be sent as application/json".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_http(controller: Arc<Controller>, address: SocketAddr, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let checked = check_request(&request, address);
+    let (status, response) = match (checked, request.method(), request.uri().path()) {
+        (Err((status, error)), _, _) => (status, ControlResponse::error(error)),
+        (Ok(()), &Method::GET, "/status") => (StatusCode::OK, controller.handle(ControlRequest::Status).await),
+        (Ok(()), &Method::POST, "/command") => {
+            let body = hyper::body::to_bytes(request.into_body()).await;
+            match body.map(|body| serde_json::from_slice::<ControlRequest>(&body)) {
+                Ok(Ok(command)) => {
+                    let response = controller.handle(command).await;
+                    (if response.ok { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY }, response)
+                },
+                Ok(Err(e)) => (StatusCode::BAD_REQUEST, ControlResponse::error(format!("Invalid command: {}", e))),
+                Err(e) => (StatusCode::BAD_REQUEST, ControlResponse::error(format!("Failed to read body: {}", e)))
+            }
+        },
+        _ => (StatusCode::NOT_FOUND, ControlResponse::error("Use GET /status or POST /command"))
+    };
+
+    let body = serde_json::to_string(&response).expect("Responses are always serializable");
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .expect("Response is always valid"))
+}
+
+/// Serves the control endpoint until the task is aborted.
+pub async fn serve(address: SocketAddr, controller: Arc<Controller>) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let controller = Arc::clone(&controller);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| handle_http(Arc::clone(&controller), address, request)))
+        }
+    });
+
+    let server = Server::try_bind(&address)?.serve(make_service);
+    info!("Control endpoint listening on http://{}", address);
+
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "127.0.0.1:51290";
+
+    fn request(method: Method, host: Option<&str>, content_type: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder().method(method).uri("/command");
+        if let Some(host) = host {
+            builder = builder.header(header::HOST, host);
+        }
+        if let Some(content_type) = content_type {
+            builder = builder.header(header::CONTENT_TYPE, content_type);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn check(method: Method, host: Option<&str>, content_type: Option<&str>) -> Result<(), StatusCode> {
+        check_request(&request(method, host, content_type), ADDRESS.parse().unwrap()).map_err(|(status, _)| status)
+    }
+
+    #[test]
+    fn loopback_hosts_are_accepted() {
+        for host in ["127.0.0.1:51290", "localhost:51290", "LOCALHOST:51290", "[::1]:51290", "127.1.2.3:51290"] {
+            assert_eq!(check(Method::GET, Some(host), None), Ok(()), "{}", host);
+        }
+    }
+
+    #[test]
+    fn other_hosts_are_refused() {
+        for host in ["evil.example:51290", "192.168.1.2:51290", "localhost.evil.example:51290", "127.0.0.1:80", "127.0.0.1"] {
+            assert_eq!(check(Method::GET, Some(host), None), Err(StatusCode::FORBIDDEN), "{}", host);
+        }
+        assert_eq!(check(Method::GET, None, None), Err(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn commands_must_be_json() {
+        let host = Some("127.0.0.1:51290");
+        assert_eq!(check(Method::POST, host, Some("application/json")), Ok(()));
+        assert_eq!(check(Method::POST, host, Some("Application/JSON; charset=utf-8")), Ok(()));
+        for content_type in [Some("text/plain"), Some("application/x-www-form-urlencoded"), None] {
+            assert_eq!(check(Method::POST, host, content_type), Err(StatusCode::UNSUPPORTED_MEDIA_TYPE), "{:?}", content_type);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{debug, error, info};
+use tokio::sync::Mutex;
+use crate::clock;
+use crate::config::ClockConfig;
+use crate::controller::Controller;
+
+/// Lists the running processes, one backend per platform.
+pub trait ProcessList: Send + Sync {
+    /// Executable names of the running processes, oldest first.
+    /// A process may show up under several names, e.g. its command and the file it was started from.
+    fn executables(&self) -> Result<Vec<String>, String>;
+}
+
+/// Reads the processes from `/proc`.
+#[cfg(target_os = "linux")]
+pub struct ProcFs;
+
+#[cfg(target_os = "linux")]
+impl ProcessList for ProcFs {
+    fn executables(&self) -> Result<Vec<String>, String> {
+        let entries = std::fs::read_dir("/proc").map_err(|e| format!("Failed to read /proc: {}", e))?;
+
+        // PIDs wrap around, so the order comes from the start time
+        let mut processes: Vec<(u64, u32)> = entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter_map(|pid| Some((start_time(pid)?, pid)))
+            .collect();
+        processes.sort_unstable();
+
+        let mut names = vec![];
+        for (_, pid) in processes {
+            // Processes may exit while we look at them, skip what can't be read
+            if let Ok(comm) = std::fs::read_to_string(format!("/proc/{}/comm", pid)) {
+                names.push(comm.trim_end().to_string());
+            }
+
+            // `comm` is cut to 15 characters, and Wine games show up under the path of their .exe
+            if let Ok(cmdline) = std::fs::read(format!("/proc/{}/cmdline", pid)) {
+                let program = cmdline.split(|b| *b == 0).next().unwrap_or_default();
+                let program = String::from_utf8_lossy(program);
+                if let Some(name) = program.rsplit(['/', '\\']).next().filter(|name| !name.is_empty()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+/// Clock ticks between the boot and the start of the process, field 22 of `/proc/<pid>/stat`.
+#[cfg(target_os = "linux")]
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command in field 2 may contain spaces and parentheses, the fields after it start at field 3
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(22 - 3)?.parse().ok()
+}
+
+/// The process list of this platform, if there is one.
+pub fn process_list() -> Option<Box<dyn ProcessList>> {
+    #[cfg(target_os = "linux")]
+    return Some(Box::new(ProcFs));
+
+    #[cfg(not(target_os = "linux"))]
+    return None;
+}
+
+/// Display name of the most recently started game among the executables.
+fn detect<'a>(games: &'a HashMap<String, String>, executables: &[String]) -> Option<&'a str> {
+    executables.iter().rev().find_map(|executable| {
+        games.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(executable))
+            .map(|(_, display)| display.as_str())
+    })
+}
+
+/// Puts the running game in `curr_game`, unless the name was set by hand not long ago.
+pub struct GameWatcher {
+    controller: Arc<Controller>,
+    config: Arc<Mutex<ClockConfig>>,
+    processes: Box<dyn ProcessList>
+}
+
+impl GameWatcher {
+    pub fn new(controller: Arc<Controller>, config: Arc<Mutex<ClockConfig>>, processes: Box<dyn ProcessList>) -> GameWatcher {
+        GameWatcher {
+            controller,
+            config,
+            processes
+        }
+    }
+
+    /// Runs until the task is aborted. Does nothing while no games are configured.
+    pub async fn run(self) {
+        loop {
+            let config = self.config.lock().await.clone();
+            if !config.games.is_empty() {
+                self.scan(&config).await;
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.game_scan_secs)).await;
+        }
+    }
+
+    async fn scan(&self, config: &ClockConfig) {
+        if self.is_overridden(config).await {
+            return;
+        }
+
+        let executables = match self.processes.executables() {
+            Ok(executables) => executables,
+            Err(e) => {
+                error!("Failed to list processes: {}", e);
+                return;
+            }
+        };
+
+        // Checked again while holding the lock, a `set` may have come in during the scan
+        let manual_since = self.controller.manual_game_since.lock().await;
+        if manual_since.is_some() {
+            return;
+        }
+
+        let detected = detect(&config.games, &executables).unwrap_or("IDLE");
+        let mut game_name = self.controller.game_name.lock().await;
+        if *game_name != detected {
+            debug!("Scanned {} executables", executables.len());
+            info!("Detected game: {}", detected);
+            *game_name = detected.to_string();
+            self.controller.screen.refresh(clock::PROVIDER);
+        }
+    }
+
+    /// Whether a name set by hand still wins, forgets it once it timed out.
+    async fn is_overridden(&self, config: &ClockConfig) -> bool {
+        let mut manual_since = self.controller.manual_game_since.lock().await;
+        match *manual_since {
+            Some(since) if since.elapsed() < Duration::from_secs(config.game_override_secs) => true,
+            Some(_) => {
+                info!("Game name set by hand timed out, detecting the game again");
+                *manual_since = None;
+                false
+            },
+            None => false
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sse_oled_controller::api::SSEngineAPI;
+use sse_oled_controller::api_types::{C2SGameCreate, C2SGameEventBind, C2SGameEventCreate, C2SGameEventRemove};
+use sse_oled_controller::types::{Handler, Icon};
+
+/// A screen layout file, a list of events with the handlers bound to them.
+/// The events go to the controller's game, or to the game of the `game` section when the file has one.
+///
+/// ```json
+/// {
+///   "game": { "game": "BUILD_MONITOR", "game_display_name": "Build Monitor" },
+///   "events": [{
+///     "event": "BUILD_STATUS",
+///     "icon_id": 16,
+///     "handlers": [{
+///       "datas": [{ "lines": [
+///         { "has-text": true, "prefix": "Build ", "context-frame-key": "status" },
+///         { "has-progress-bar": true }
+///       ]}]
+///     }]
+///   }]
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Layout {
+    /// The `game` sections of the files
+    pub games: Vec<LayoutGame>,
+    pub events: Vec<LayoutEvent>
+}
+
+/// The `game` section of a layout file, registered like the controller's game but without heartbeats.
+#[derive(Debug)]
+pub struct LayoutGame {
+    pub metadata: C2SGameCreate,
+    /// Line and column of the section in its file
+    pub position: (usize, usize)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LayoutEvent {
+    /// Game of the `game` section of the file, the controller's game when there is none
+    #[serde(skip)]
+    pub game: Option<String>,
+    /// Line and column of the event in its file
+    #[serde(skip)]
+    pub position: (usize, usize),
+    pub event: String,
+    #[serde(default)]
+    pub min_value: i32,
+    #[serde(default = "LayoutEvent::default_max_value")]
+    pub max_value: i32,
+    #[serde(default = "LayoutEvent::default_icon")]
+    pub icon_id: Icon,
+    #[serde(default)]
+    pub value_optional: bool,
+    pub handlers: Vec<Handler>
+}
+
+impl LayoutEvent {
+    fn default_max_value() -> i32 {
+        100
+    }
+
+    fn default_icon() -> Icon {
+        Icon::NoIcon
+    }
+
+    pub fn to_create(&self, game: &str) -> C2SGameEventCreate {
+        C2SGameEventCreate {
+            game: game.to_string(),
+            event: self.event.clone(),
+            min_value: self.min_value,
+            max_value: self.max_value,
+            icon_id: self.icon_id,
+            value_optional: self.value_optional,
+        }
+    }
+
+    pub fn into_bind(self, game: &str) -> C2SGameEventBind {
+        C2SGameEventBind {
+            game: game.to_string(),
+            event: self.event,
+            min_value: self.min_value,
+            max_value: self.max_value,
+            icon_id: self.icon_id,
+            handlers: self.handlers
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawLayout<'a> {
+    #[serde(borrow, default)]
+    game: Option<&'a RawValue>,
+    #[serde(borrow)]
+    events: Vec<&'a RawValue>
+}
+
+#[derive(Deserialize)]
+struct RawEvent<'a> {
+    #[serde(borrow, default)]
+    handlers: Vec<&'a RawValue>
+}
+
+/// A problem found in a layout file, with the position it was found at.
+#[derive(Debug)]
+pub struct LayoutError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl Display for LayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.path.display(), self.line, self.column, self.message)
+    }
+}
+
+impl Layout {
+    /// `reserved` are the events the screen providers bind to the controller's game, layouts can't replace them.
+    pub fn load(path: impl AsRef<Path>, reserved: &HashSet<String>) -> Result<Layout, Vec<LayoutError>> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| vec![LayoutError {
+            path: path.to_path_buf(),
+            line: 0,
+            column: 0,
+            message: format!("Failed to read layout: {}", e)
+        }])?;
+
+        Layout::parse(path, &content, reserved)
+    }
+
+    /// Parses and validates a layout, every event is checked so that all errors are reported at once.
+    pub fn parse(path: &Path, content: &str, reserved: &HashSet<String>) -> Result<Layout, Vec<LayoutError>> {
+        let error = |(line, column): (usize, usize), message: String| LayoutError {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message
+        };
+
+        let raw: RawLayout = serde_json::from_str(content)
+            .map_err(|e| vec![error((e.line(), e.column()), message_of(&e))])?;
+
+        let mut events = vec![];
+        let mut errors = vec![];
+        let mut names = HashSet::new();
+
+        let mut game = None;
+        if let Some(raw_game) = raw.game {
+            let start = position_of(content, raw_game.get());
+            match serde_json::from_str::<C2SGameCreate>(raw_game.get()) {
+                Ok(metadata) if metadata.game.is_empty() => errors.push(error(start, "Game name must not be empty".to_string())),
+                Ok(metadata) => game = Some(LayoutGame {
+                    metadata,
+                    position: start
+                }),
+                Err(e) => errors.push(error(relative_to(start, &e), format!("game: {}", message_of(&e))))
+            }
+        }
+        let game_name = game.as_ref().map(|game| game.metadata.game.clone());
+
+        for raw_event in raw.events {
+            let start = position_of(content, raw_event.get());
+
+            let mut event: LayoutEvent = match serde_json::from_str(raw_event.get()) {
+                Ok(event) => event,
+                Err(e) => {
+                    // An error in a handler is only found once the whole handler was read, parse it on its own to find where
+                    let in_handler = handler_error(raw_event.get()).map(|(i, raw_handler, e)| {
+                        let start = position_of(content, raw_handler.get());
+                        error(relative_to(start, &e), format!("handlers[{}]: {}", i, message_of(&e)))
+                    });
+                    errors.push(in_handler.unwrap_or_else(|| error(relative_to(start, &e), message_of(&e))));
+                    continue;
+                }
+            };
+
+            if event.event.is_empty() {
+                errors.push(error(start, "Event name must not be empty".to_string()));
+            } else if !names.insert(event.event.clone()) {
+                errors.push(error(start, format!("Event {} is defined more than once", event.event)));
+            } else if game_name.is_none() && reserved.contains(&event.event) {
+                errors.push(error(start, format!("Event {} is bound by a screen provider, use another name", event.event)));
+            }
+
+            if event.min_value > event.max_value {
+                errors.push(error(start, format!("Event {} has min_value greater than max_value", event.event)));
+            }
+
+            if event.handlers.is_empty() {
+                errors.push(error(start, format!("Event {} has no handlers", event.event)));
+            }
+
+            // Handlers the engine would partly ignore are only warned about by `SSEngineAPI::bind_event`
+
+            event.game = game_name.clone();
+            event.position = start;
+            events.push(event);
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Layout {
+            games: game.into_iter().collect(),
+            events
+        })
+    }
+
+    /// Loads several layout files as one, event names must be unique in each game across all of them,
+    /// and a game can only be described by one file.
+    pub fn load_all(paths: &[String], reserved: &HashSet<String>) -> Result<Layout, Vec<LayoutError>> {
+        let mut games: Vec<LayoutGame> = vec![];
+        let mut events = vec![];
+        let mut errors = vec![];
+        let mut names = HashSet::new();
+
+        for path in paths {
+            match Layout::load(path, reserved) {
+                Ok(layout) => {
+                    for game in layout.games {
+                        if games.iter().any(|other| other.metadata.game == game.metadata.game) {
+                            errors.push(LayoutError {
+                                path: PathBuf::from(path),
+                                line: game.position.0,
+                                column: game.position.1,
+                                message: format!("Game {} is already described in another layout", game.metadata.game)
+                            });
+                        }
+                        games.push(game);
+                    }
+
+                    for event in layout.events {
+                        if !names.insert((event.game.clone(), event.event.clone())) {
+                            errors.push(LayoutError {
+                                path: PathBuf::from(path),
+                                line: event.position.0,
+                                column: event.position.1,
+                                message: format!("Event {} is already defined in another layout", event.event)
+                            });
+                        }
+                        events.push(event);
+                    }
+                },
+                Err(e) => errors.extend(e)
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(Layout {
+            games,
+            events
+        })
+    }
+}
+
+/// Keeps track of the layout games and events registered to the engine,
+/// so that a changed layout only sends the requests needed to catch up.
+pub struct LayoutRegistry {
+    game: String,
+    /// Events the screen providers bind to `game`
+    reserved: HashSet<String>,
+    /// Game name to its serialized metadata
+    games: HashMap<String, String>,
+    /// Game and event name to the serialized definition of the event
+    registered: HashMap<(String, String), String>
+}
+
+impl LayoutRegistry {
+    pub fn new(game: &str, reserved: HashSet<String>) -> LayoutRegistry {
+        LayoutRegistry {
+            game: game.to_string(),
+            reserved,
+            games: HashMap::new(),
+            registered: HashMap::new()
+        }
+    }
+
+    /// Events layouts can't use in the controller's game, see `Layout::load`.
+    pub fn reserved(&self) -> &HashSet<String> {
+        &self.reserved
+    }
+
+    /// Registers the changed games, removes the events missing from the layout,
+    /// creates the new ones and rebinds the changed ones.
+    pub async fn apply(&mut self, api: &SSEngineAPI, layout: Layout) -> Result<(), reqwest::Error> {
+        for game in &layout.games {
+            let metadata = serde_json::to_string(&game.metadata).expect("Games are always serializable");
+            if self.games.get(&game.metadata.game) != Some(&metadata) {
+                info!("Registering layout game {}", game.metadata.game);
+                api.register_game(&game.metadata).await?;
+                self.games.insert(game.metadata.game.clone(), metadata);
+            }
+        }
+
+        let keys: HashSet<(String, String)> = layout.events.iter().map(|event| self.key(event)).collect();
+        let removed: Vec<(String, String)> = self.registered.keys()
+            .filter(|key| !keys.contains(*key))
+            .cloned()
+            .collect();
+
+        for (game, name) in removed {
+            info!("Removing layout event {} of {}", name, game);
+            api.remove_event(C2SGameEventRemove {
+                game: game.clone(),
+                event: name.clone()
+            }).await?;
+            self.registered.remove(&(game, name));
+        }
+
+        for event in layout.events {
+            let definition = serde_json::to_string(&event).expect("Layout events are always serializable");
+            let key = self.key(&event);
+            let game = key.0.clone();
+            match self.registered.get(&key) {
+                Some(registered) if *registered == definition => continue,
+                Some(_) => {
+                    info!("Rebinding layout event {} of {}", key.1, game);
+                    api.bind_event(event.into_bind(&game)).await?;
+                },
+                None => {
+                    info!("Registering layout event {} of {}", key.1, game);
+                    api.new_event(event.to_create(&game)).await?;
+                    api.bind_event(event.into_bind(&game)).await?;
+                }
+            }
+            self.registered.insert(key, definition);
+        }
+
+        Ok(())
+    }
+
+    fn key(&self, event: &LayoutEvent) -> (String, String) {
+        (event.game.clone().unwrap_or_else(|| self.game.clone()), event.event.clone())
+    }
+}
+
+/// Reads GoLisp handler files as one program, since each upload replaces the handlers loaded before.
+pub fn load_golisp(paths: &[String]) -> Result<String, String> {
+    let mut golisp = String::new();
+    for path in paths {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        golisp.push_str(&content);
+        golisp.push('\n');
+    }
+
+    Ok(golisp)
+}
+
+/// The first handler of the event that fails to parse, with its index and the error.
+fn handler_error(raw_event: &str) -> Option<(usize, &RawValue, serde_json::Error)> {
+    let event: RawEvent = serde_json::from_str(raw_event).ok()?;
+    event.handlers.into_iter().enumerate()
+        .find_map(|(i, handler)| Handler::from_json(handler.get()).err().map(|e| (i, handler, e)))
+}
+
+/// Position in the file of an error of a part that starts at `start`.
+fn relative_to(start: (usize, usize), e: &serde_json::Error) -> (usize, usize) {
+    if e.line() == 1 {
+        (start.0, start.1 + e.column() - 1)
+    } else {
+        (start.0 + e.line() - 1, e.column())
+    }
+}
+
+/// Line and column (both starting at 1) of `part`, which must be a slice of `content`.
+fn position_of(content: &str, part: &str) -> (usize, usize) {
+    let offset = part.as_ptr() as usize - content.as_ptr() as usize;
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+    (line, column)
+}
+
+/// The error message without the position serde_json appends, since it's relative to the event.
+fn message_of(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    match message.rfind(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc};
+use std::time::Duration;
+use log::{error, info, warn};
+use tokio::sync::Mutex;
+use sse_oled_controller::api::SSEngineAPI;
+use sse_oled_controller::api_types::C2SGameCreate;
+use sse_oled_controller::scheduler::{Provider, Scheduler};
+use crate::commands::ReplContext;
+use crate::config::ClockConfig;
+use crate::layout::{Layout, LayoutRegistry};
+use crate::reload::Reloader;
+use crate::controller::Controller;
+use crate::clock::ClockProvider;
+use crate::games::GameWatcher;
+use crate::media::MediaProvider;
+use crate::metrics::{Metric, MetricProvider, MetricsSampler};
+use crate::notifications::NotificationProvider;
+use crate::timers::TimersProvider;
+use crate::voice::{VoiceProvider, VoiceState};
+
+mod config;
+mod clock;
+mod timers;
+mod layout;
+mod reload;
+mod repl;
+mod commands;
+mod controller;
+mod notifications;
+mod games;
+mod metrics;
+mod media;
+mod voice;
+
+#[tokio::main]
+async fn main() {
+    std::env::set_var("RUST_LOG", "info");
+    env_logger::init();
+
+    info!("SSE OLED Clock 1.0 by lamadaemon");
+
+    warn!("Early access software, robustness is not guaranteed!");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let config = match ClockConfig::from_args(args.clone()) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("{}", ClockConfig::USAGE);
+            return;
+        },
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut api = SSEngineAPI::new(config.server.as_deref());
+    api.setup(C2SGameCreate {
+        game: clock::GAME.to_string(),
+        game_display_name: Some("OLED Clock".to_string()),
+        developer: Some("lamadaemon".to_string()),
+        deinitialize_timer_length_ms: Some(15000u16),
+    }).await.expect("Failed to setup SSE API");
+
+    let api = Arc::new(api);
+    let screens = config.screens.clone();
+    let metrics = config.metrics.clone();
+    let config = Arc::new(Mutex::new(config));
+
+    let scheduler = Scheduler::new(Arc::clone(&api), screens);
+    let controller = Arc::new(Controller::new(Arc::clone(&api), scheduler.handle()));
+    let playback = Arc::new(Mutex::new(None));
+    let voice_state = Arc::new(Mutex::new(VoiceState::new()));
+    let mut scheduler = scheduler
+        .provider(Provider::new("notifications", NotificationProvider::new(Arc::clone(&controller)))
+            .priority(200)
+            .refresh(Duration::from_millis(100)))
+        .provider(Provider::new("timers", TimersProvider::new(Arc::clone(&controller)))
+            .priority(100))
+        .provider(Provider::new(voice::PROVIDER, VoiceProvider::new(Arc::clone(&voice_state)))
+            .priority(50))
+        .provider(Provider::new(clock::PROVIDER, ClockProvider::new(Arc::clone(&config), Arc::clone(&controller)))
+            .min_dwell(Duration::from_secs(10)))
+        .provider(Provider::new(media::PROVIDER, MediaProvider::new(Arc::clone(&playback)))
+            .min_dwell(Duration::from_secs(10))
+            .refresh(media::SCROLL_STEP));
+
+    // Each metric takes turns with the clock
+    let sampler = MetricsSampler::new(Arc::clone(&controller), &metrics);
+    for metric in Metric::ALL.into_iter().filter(|metric| metrics.contains_key(metric)) {
+        let metric_config = metrics[&metric].clone();
+        let refresh = Duration::from_millis(metric_config.refresh_millis);
+        scheduler = scheduler.provider(Provider::new(metric.name(), MetricProvider::new(metric, metric_config, sampler.samples()))
+            .min_dwell(Duration::from_secs(5))
+            .refresh(refresh));
+    }
+    // Layouts can't take the events of the providers
+    let mut registry = LayoutRegistry::new(clock::GAME, scheduler.event_names().await);
+    let layouts = config.lock().await.layouts.clone();
+    let layout = Layout::load_all(&layouts, registry.reserved()).unwrap_or_else(|errors| {
+        errors.iter().for_each(|e| error!("{}", e));
+        std::process::exit(1);
+    });
+    registry.apply(&api, layout).await.expect("Failed to register layout events");
+    let golisp = config.lock().await.golisp.clone();
+    if !golisp.is_empty() {
+        let golisp = layout::load_golisp(&golisp).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        api.load_golisp_handlers(golisp).await.expect("Failed to upload GoLisp handlers");
+    }
+
+    let sampler_task = tokio::spawn(sampler.run());
+    let media_task = media::spawn_watcher(Arc::clone(&controller), playback);
+    scheduler.bind().await.expect("Failed to create screen events");
+    let providers = scheduler.names();
+    let scheduler_task = tokio::spawn(scheduler.run());
+
+    let reload_task = tokio::spawn(Reloader::new(Arc::clone(&api), Arc::clone(&config), Arc::clone(&controller.screen),
+        args, registry).run());
+
+    let game_task = games::process_list().map(|processes| {
+        tokio::spawn(GameWatcher::new(Arc::clone(&controller), Arc::clone(&config), processes).run())
+    });
+    if game_task.is_none() && !config.lock().await.games.is_empty() {
+        warn!("Game detection is not available on this platform, use 'set' instead");
+    }
+
+    let control_task = config.lock().await.control.clone().map(|address| {
+        let address = address.parse().expect("Control address is validated with the config");
+        let controller = Arc::clone(&controller);
+        tokio::spawn(async move {
+            if let Err(e) = controller::serve(address, controller).await {
+                error!("Control endpoint stopped: {}", e);
+            }
+        })
+    });
+
+    let voice_task = config.lock().await.voice.clone().map(|address| {
+        let address = address.parse().expect("Voice address is validated with the config");
+        let controller = Arc::clone(&controller);
+        tokio::spawn(async move {
+            if let Err(e) = voice::serve(address, controller, voice_state).await {
+                error!("Voice socket stopped: {}", e);
+            }
+        })
+    });
+
+    info!("Setup complete, type 'help' for a list of commands");
+    let context = ReplContext {
+        controller
+    };
+    tokio::task::spawn_blocking(move || repl::run(commands::commands(&providers), context))
+        .await.expect("Failed to run command prompt");
+
+    info!("Exiting...");
+    reload_task.abort();
+    sampler_task.abort();
+    if let Some(media_task) = media_task {
+        media_task.abort();
+    }
+    if let Some(game_task) = game_task {
+        game_task.abort();
+    }
+    if let Some(control_task) = control_task {
+        control_task.abort();
+    }
+    if let Some(voice_task) = voice_task {
+        voice_task.abort();
+    }
+    scheduler_task.abort();
+    api.done().await.expect("Failed to deinitialize SSE API");
+}
@@ -0,0 +1,564 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use log::debug;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use sse_oled_controller::api_types::C2SGameEventCreate;
+use sse_oled_controller::payload::EventPayload;
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::screen::{progress_bar, text, Screen};
+use sse_oled_controller::types::Icon;
+use crate::clock::GAME;
+use crate::controller::Controller;
+use crate::notifications::Notification;
+
+pub const PROVIDER: &str = "media";
+const PLAYING: &str = "MEDIA_PLAYING";
+const PAUSED: &str = "MEDIA_PAUSED";
+
+/// How often the text moves by one character, the refresh rate of the provider.
+pub const SCROLL_STEP: Duration = Duration::from_millis(300);
+/// Characters that fit on a line next to the icon.
+const SCROLL_WIDTH: usize = 16;
+/// Steps the start of the text stays still before it scrolls.
+const SCROLL_PAUSE: usize = 5;
+const SCROLL_GAP: &str = "    ";
+
+/// How long a paused player stays on the screen.
+const PAUSE_LINGER: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub title: String,
+    pub artist: String
+}
+
+/// What the followed player is playing.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    pub player: String,
+    pub track: Track,
+    pub playing: bool,
+    /// Position when it was read at `at`
+    pub position: Duration,
+    pub length: Option<Duration>,
+    pub at: Instant,
+    /// When the player last started or stopped playing
+    pub since: Instant
+}
+
+impl Playback {
+    /// Position at `now`, moved on from the last reading while playing.
+    fn position(&self, now: Instant) -> Duration {
+        let position = match self.playing {
+            true => self.position + now.saturating_duration_since(self.at),
+            false => self.position
+        };
+        self.length.map_or(position, |length| position.min(length))
+    }
+
+    /// Percent of the track played, 0 when the player doesn't know the length.
+    fn progress(&self, now: Instant) -> i32 {
+        match self.length.filter(|length| !length.is_zero()) {
+            Some(length) => (self.position(now).as_secs_f64() * 100.0 / length.as_secs_f64()).round() as i32,
+            None => 0
+        }
+    }
+}
+
+/// The part of the text shown after `step` refreshes, scrolling when it doesn't fit on a line.
+fn scroll(text: &str, step: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= SCROLL_WIDTH {
+        return text.to_string();
+    }
+
+    let looped: Vec<char> = chars.into_iter().chain(SCROLL_GAP.chars()).collect();
+    let offset = (step % (looped.len() + SCROLL_PAUSE)).saturating_sub(SCROLL_PAUSE);
+    looped.iter().cycle().skip(offset).take(SCROLL_WIDTH).collect()
+}
+
+/// MPRIS doesn't signal the position, so the players are polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const TRACK_NOTIFY_DURATION: Duration = Duration::from_secs(3);
+const TRACK_NOTIFY_PRIORITY: u8 = 5;
+
+/// The playback state of a player, as read from its properties.
+#[derive(Clone, Debug)]
+pub struct Status {
+    pub track: Track,
+    pub playing: bool,
+    pub paused: bool,
+    pub position: Duration,
+    pub length: Option<Duration>
+}
+
+/// The media players of the platform, MPRIS on Linux.
+#[async_trait]
+pub trait Players: Send + Sync {
+    /// Names of the running players.
+    async fn names(&self) -> Result<Vec<String>, String>;
+
+    async fn status(&self, player: &str) -> Result<Status, String>;
+}
+
+/// What a poll changed.
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    /// The player, track or play state changed, so the screen should be redrawn
+    screen: bool,
+    /// A track started playing that wasn't announced yet
+    new_track: Option<Track>
+}
+
+/// Keeps the playback of one of the players.
+struct Follower<P> {
+    players: P,
+    playback: Arc<Mutex<Option<Playback>>>,
+    /// Last track announced, so that pausing and playing it again doesn't announce it twice
+    last_track: Option<Track>
+}
+
+impl<P: Players> Follower<P> {
+    /// The player that plays, preferring the one followed so far, or else the followed one if it's paused.
+    async fn poll(&self) -> Result<Option<(String, Status)>, String> {
+        let current = self.playback.lock().await.as_ref().map(|playback| playback.player.clone());
+
+        let mut players = self.players.names().await?;
+        players.sort_by_key(|player| Some(player) != current.as_ref());
+
+        let mut paused = None;
+        for player in players {
+            // Players may quit while we look at them
+            let Ok(status) = self.players.status(&player).await else { continue };
+            if status.playing {
+                return Ok(Some((player, status)));
+            }
+            if status.paused && Some(&player) == current.as_ref() {
+                paused = Some((player, status));
+            }
+        }
+
+        Ok(paused)
+    }
+
+    async fn update(&mut self, polled: Option<(String, Status)>, now: Instant) -> Changes {
+        let mut playback = self.playback.lock().await;
+        let previous = playback.take();
+
+        let Some((player, status)) = polled else {
+            return Changes {
+                screen: previous.is_some(),
+                new_track: None
+            };
+        };
+
+        let screen = match &previous {
+            Some(previous) => previous.player != player || previous.playing != status.playing || previous.track != status.track,
+            None => true
+        };
+        let since = match &previous {
+            Some(previous) if previous.player == player && previous.playing == status.playing => previous.since,
+            _ => now
+        };
+
+        let new_track = status.playing && self.last_track.as_ref() != Some(&status.track);
+        if new_track {
+            self.last_track = Some(status.track.clone());
+        }
+
+        *playback = Some(Playback {
+            player,
+            track: status.track.clone(),
+            playing: status.playing,
+            position: status.position,
+            length: status.length,
+            at: now,
+            since
+        });
+
+        Changes {
+            screen,
+            new_track: new_track.then_some(status.track)
+        }
+    }
+}
+
+/// Follows the players and announces new tracks.
+pub struct MediaWatcher<P> {
+    controller: Arc<Controller>,
+    follower: Follower<P>
+}
+
+impl<P: Players> MediaWatcher<P> {
+    pub fn new(controller: Arc<Controller>, players: P, playback: Arc<Mutex<Option<Playback>>>) -> MediaWatcher<P> {
+        MediaWatcher {
+            controller,
+            follower: Follower {
+                players,
+                playback,
+                last_track: None
+            }
+        }
+    }
+
+    /// Runs until the task is aborted.
+    pub async fn run(mut self) {
+        loop {
+            match self.follower.poll().await {
+                Ok(polled) => {
+                    let now = Instant::now();
+                    let changes = self.follower.update(polled, now).await;
+                    self.apply(changes, now).await;
+                },
+                Err(e) => debug!("Failed to read the media players: {}", e)
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn apply(&self, changes: Changes, now: Instant) {
+        if let Some(track) = changes.new_track {
+            let text = match track.artist.is_empty() {
+                true => track.title,
+                false => format!("{} - {}", track.title, track.artist)
+            };
+            self.controller.notify(Notification {
+                text,
+                icon: Icon::Music,
+                duration: TRACK_NOTIFY_DURATION,
+                priority: TRACK_NOTIFY_PRIORITY,
+                blinks: None,
+                expires_at: Some(now + TRACK_NOTIFY_DURATION)
+            }).await;
+        }
+
+        if changes.screen {
+            self.controller.screen.refresh(PROVIDER);
+        }
+    }
+}
+
+/// The MPRIS players of the session bus.
+#[cfg(target_os = "linux")]
+pub mod mpris {
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use async_trait::async_trait;
+    use zbus::fdo::{DBusProxy, PropertiesProxy};
+    use zbus::names::InterfaceName;
+    use zbus::zvariant::{OwnedValue, Value};
+    use zbus::Connection;
+    use super::{Players, Status, Track};
+
+    const PREFIX: &str = "org.mpris.MediaPlayer2.";
+    const PATH: &str = "/org/mpris/MediaPlayer2";
+    const INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+    fn string(value: &Value) -> Option<String> {
+        match value {
+            Value::Value(inner) => string(inner),
+            Value::Str(text) => Some(text.to_string()),
+            // `xesam:artist` is a list of names
+            Value::Array(items) => Some(items.iter().filter_map(string).collect::<Vec<_>>().join(", ")),
+            _ => None
+        }
+    }
+
+    /// Microseconds as a duration, players differ in the integer type they use.
+    fn micros(value: &Value) -> Option<Duration> {
+        let micros = match value {
+            Value::Value(inner) => return micros(inner),
+            Value::I64(micros) => u64::try_from(*micros).ok()?,
+            Value::U64(micros) => *micros,
+            Value::I32(micros) => u64::try_from(*micros).ok()?,
+            Value::U32(micros) => u64::from(*micros),
+            _ => return None
+        };
+        Some(Duration::from_micros(micros))
+    }
+
+    async fn status(connection: &Connection, player: &str) -> zbus::Result<Status> {
+        let properties = PropertiesProxy::builder(connection)
+            .destination(player.to_string())?
+            .path(PATH)?
+            .build().await?;
+        let all = properties.get_all(Some(InterfaceName::from_static_str_unchecked(INTERFACE)).into()).await?;
+
+        let property = |name: &str| all.get(name).map(|value| &**value);
+        let metadata: HashMap<String, OwnedValue> = match all.get("Metadata") {
+            Some(metadata) => metadata.try_clone()?.try_into()?,
+            None => HashMap::new()
+        };
+        let meta = |name: &str| metadata.get(name).map(|value| &**value);
+
+        let playback = property("PlaybackStatus").and_then(string).unwrap_or_default();
+        Ok(Status {
+            track: Track {
+                title: meta("xesam:title").and_then(string).unwrap_or_default(),
+                artist: meta("xesam:artist").and_then(string).unwrap_or_default()
+            },
+            playing: playback == "Playing",
+            paused: playback == "Paused",
+            position: property("Position").and_then(micros).unwrap_or_default(),
+            length: meta("mpris:length").and_then(micros)
+        })
+    }
+
+    pub struct Mpris {
+        connection: Connection
+    }
+
+    impl Mpris {
+        /// Connects to the session bus.
+        pub async fn connect() -> zbus::Result<Mpris> {
+            Ok(Mpris {
+                connection: Connection::session().await?
+            })
+        }
+    }
+
+    #[async_trait]
+    impl Players for Mpris {
+        async fn names(&self) -> Result<Vec<String>, String> {
+            let names = DBusProxy::new(&self.connection).await.map_err(|e| e.to_string())?
+                .list_names().await.map_err(|e| e.to_string())?;
+            Ok(names.into_iter()
+                .map(|name| name.to_string())
+                .filter(|name| name.starts_with(PREFIX))
+                .collect())
+        }
+
+        async fn status(&self, player: &str) -> Result<Status, String> {
+            status(&self.connection, player).await.map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Starts following the media players of this platform, if it has a backend.
+pub fn spawn_watcher(controller: Arc<Controller>, playback: Arc<Mutex<Option<Playback>>>) -> Option<JoinHandle<()>> {
+    #[cfg(target_os = "linux")]
+    return Some(tokio::spawn(async move {
+        match mpris::Mpris::connect().await {
+            Ok(players) => MediaWatcher::new(controller, players, playback).run().await,
+            Err(e) => log::info!("No session bus, media provider disabled: {}", e)
+        }
+    }));
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (controller, playback);
+        None
+    }
+}
+
+/// Shows the title and artist of the playing track with its position, and the play or pause icon.
+pub struct MediaProvider {
+    playback: Arc<Mutex<Option<Playback>>>,
+    step: usize
+}
+
+impl MediaProvider {
+    pub fn new(playback: Arc<Mutex<Option<Playback>>>) -> MediaProvider {
+        MediaProvider {
+            playback,
+            step: 0
+        }
+    }
+}
+
+/// Data of `MEDIA_PLAYING` and `MEDIA_PAUSED`.
+#[derive(EventPayload)]
+struct MediaUpdate {
+    /// Percent of the track that has played
+    #[payload(value)]
+    progress: i32,
+    /// Scrolled to the current step
+    title: String,
+    artist: String
+}
+
+fn media_binding(event: &str, icon: Icon) -> Result<EventBinding, String> {
+    EventBinding::for_payload::<MediaUpdate>(C2SGameEventCreate {
+            game: GAME.to_string(),
+            event: event.to_string(),
+            min_value: 0,
+            max_value: 100,
+            icon_id: icon,
+            value_optional: false,
+        }, vec![Screen::new().frame(Screen::multi_line()
+            .line(text().frame_key("title"))
+            .line(text().frame_key("artist"))
+            .line(progress_bar())
+            // Outlasts the next step, so the icon stays until something else is shown
+            .length_millis(SCROLL_STEP.as_millis() as i32 * 4)
+            .icon(icon)).into()])
+}
+
+#[async_trait]
+impl ScreenProvider for MediaProvider {
+    /// One event per icon, so that play and pause don't need a rebind.
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        Ok(vec![media_binding(PLAYING, Icon::Play)?, media_binding(PAUSED, Icon::Pause)?])
+    }
+
+    async fn is_ready(&mut self, now: Instant) -> bool {
+        match &*self.playback.lock().await {
+            Some(playback) => playback.playing || now.duration_since(playback.since) < PAUSE_LINGER,
+            None => false
+        }
+    }
+
+    async fn frame(&mut self, now: Instant, fresh: bool) -> Result<Frame, reqwest::Error> {
+        self.step = if fresh { 0 } else { self.step + 1 };
+
+        let Some(playback) = self.playback.lock().await.clone() else {
+            return Ok(Frame::Unchanged);
+        };
+
+        let event = if playback.playing { PLAYING } else { PAUSED };
+        Ok(Frame::Event(event.to_string(), MediaUpdate {
+            progress: playback.progress(now),
+            title: scroll(&playback.track.title, self.step),
+            artist: scroll(&playback.track.artist, self.step)
+        }.to_event_data()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    #[test]
+    fn short_text_stays() {
+        assert_eq!(scroll("Short", 7), "Short");
+        assert_eq!(scroll("Exactly sixteen!", 100), "Exactly sixteen!");
+    }
+
+    #[test]
+    fn long_text_pauses_then_scrolls_and_loops() {
+        let text = "A rather long song title";
+        let looped = text.chars().count() + SCROLL_GAP.len();
+
+        for step in 0..=SCROLL_PAUSE {
+            assert_eq!(scroll(text, step), "A rather long so");
+        }
+        assert_eq!(scroll(text, SCROLL_PAUSE + 1), " rather long son");
+        assert_eq!(scroll(text, SCROLL_PAUSE + 20), "itle    A rather");
+        assert_eq!(scroll(text, looped + SCROLL_PAUSE), "A rather long so");
+        assert_eq!(scroll("Ünïcödé ünïcödé ünïcödé", SCROLL_PAUSE + 1).chars().count(), SCROLL_WIDTH);
+    }
+
+    /// Players with a fixed status, `None` for players that quit while being looked at.
+    #[derive(Default)]
+    struct FakePlayers {
+        players: std::sync::Mutex<Vec<(String, Option<Status>)>>
+    }
+
+    impl FakePlayers {
+        fn set(&self, players: &[(&str, Option<Status>)]) {
+            *self.players.lock().unwrap() = players.iter().map(|(name, status)| (name.to_string(), status.clone())).collect();
+        }
+    }
+
+    #[async_trait]
+    impl Players for FakePlayers {
+        async fn names(&self) -> Result<Vec<String>, String> {
+            Ok(self.players.lock().unwrap().iter().map(|(name, _)| name.clone()).collect())
+        }
+
+        async fn status(&self, player: &str) -> Result<Status, String> {
+            let players: HashMap<_, _> = self.players.lock().unwrap().iter().cloned().collect();
+            players.get(player).cloned().flatten().ok_or("Player quit".to_string())
+        }
+    }
+
+    fn status(title: &str, playing: bool) -> Option<Status> {
+        Some(Status {
+            track: Track {
+                title: title.to_string(),
+                artist: String::new()
+            },
+            playing,
+            paused: !playing,
+            position: Duration::ZERO,
+            length: None
+        })
+    }
+
+    fn follower() -> Follower<FakePlayers> {
+        Follower {
+            players: FakePlayers::default(),
+            playback: Arc::new(Mutex::new(None)),
+            last_track: None
+        }
+    }
+
+    async fn step(follower: &mut Follower<FakePlayers>, now: Instant) -> Changes {
+        let polled = follower.poll().await.unwrap();
+        follower.update(polled, now).await
+    }
+
+    fn track(title: &str) -> Option<Track> {
+        status(title, true).map(|status| status.track)
+    }
+
+    #[tokio::test]
+    async fn new_tracks_are_announced_once() {
+        let start = Instant::now();
+        let mut follower = follower();
+
+        follower.players.set(&[("a", status("One", true))]);
+        assert_eq!(step(&mut follower, start).await, Changes { screen: true, new_track: track("One") });
+        assert_eq!(step(&mut follower, start).await, Changes::default());
+
+        // Pausing and playing it again isn't a new track
+        follower.players.set(&[("a", status("One", false))]);
+        assert_eq!(step(&mut follower, start).await, Changes { screen: true, new_track: None });
+        follower.players.set(&[("a", status("One", true))]);
+        assert_eq!(step(&mut follower, start).await, Changes { screen: true, new_track: None });
+
+        follower.players.set(&[("a", status("Two", true))]);
+        assert_eq!(step(&mut follower, start).await, Changes { screen: true, new_track: track("Two") });
+    }
+
+    #[tokio::test]
+    async fn the_followed_player_wins_and_stays_while_paused() {
+        let start = Instant::now();
+        let mut follower = follower();
+
+        follower.players.set(&[("a", status("One", true)), ("b", status("Two", true))]);
+        step(&mut follower, start).await;
+        follower.players.set(&[("b", status("Two", true)), ("a", status("One", true))]);
+        assert_eq!(step(&mut follower, start).await, Changes::default());
+
+        // Paused, it stays followed until another one plays
+        let later = start + Duration::from_secs(5);
+        follower.players.set(&[("a", status("One", false)), ("b", status("Two", false))]);
+        step(&mut follower, later).await;
+        let playback = follower.playback.lock().await.clone().unwrap();
+        assert_eq!((playback.player.as_str(), playback.playing, playback.since), ("a", false, later));
+
+        follower.players.set(&[("a", status("One", false)), ("b", status("Two", true))]);
+        assert_eq!(step(&mut follower, later).await, Changes { screen: true, new_track: track("Two") });
+        assert_eq!(follower.playback.lock().await.as_ref().unwrap().player, "b");
+    }
+
+    #[tokio::test]
+    async fn players_that_quit_are_skipped() {
+        let start = Instant::now();
+        let mut follower = follower();
+
+        follower.players.set(&[("a", None), ("b", status("Two", true))]);
+        step(&mut follower, start).await;
+        assert_eq!(follower.playback.lock().await.as_ref().unwrap().player, "b");
+
+        follower.players.set(&[]);
+        assert_eq!(step(&mut follower, start).await, Changes { screen: true, new_track: None });
+        assert!(follower.playback.lock().await.is_none());
+        assert_eq!(step(&mut follower, start).await, Changes::default());
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use sse_oled_controller::api_types::C2SGameEventCreate;
+use sse_oled_controller::payload::EventPayload;
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::screen::{progress_bar, text, Screen};
+use sse_oled_controller::types::Icon;
+use crate::clock::GAME;
+use crate::config::MetricConfig;
+use crate::controller::Controller;
+use crate::notifications::Notification;
+
+/// Priority of the notification shown when a metric goes above its threshold.
+const ALERT_PRIORITY: u8 = 10;
+const ALERT_DURATION: Duration = Duration::from_secs(3);
+const ALERT_BLINKS: i32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    Cpu,
+    Memory,
+    Temperature,
+    Network
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::Cpu, Metric::Memory, Metric::Temperature, Metric::Network];
+
+    /// Name in the config, also the name of its screen provider.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Cpu => "cpu",
+            Metric::Memory => "memory",
+            Metric::Temperature => "temperature",
+            Metric::Network => "network"
+        }
+    }
+
+    pub fn event(self) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU_LOAD",
+            Metric::Memory => "MEMORY_USAGE",
+            Metric::Temperature => "TEMPERATURE",
+            Metric::Network => "NETWORK_THROUGHPUT"
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU",
+            Metric::Memory => "Memory",
+            Metric::Temperature => "Temperature",
+            Metric::Network => "Network"
+        }
+    }
+
+    fn icon(self) -> Icon {
+        match self {
+            Metric::Cpu => Icon::CPU,
+            Metric::Memory => Icon::RAM,
+            Metric::Temperature => Icon::Temperature,
+            Metric::Network => Icon::Connect
+        }
+    }
+
+    /// Value of a full progress bar when the config doesn't say: percent, °C or KiB/s.
+    fn default_max(self) -> f64 {
+        match self {
+            Metric::Cpu | Metric::Memory | Metric::Temperature => 100.0,
+            // 100 Mbit/s
+            Metric::Network => 12500.0
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Metric::ALL.into_iter()
+            .find(|metric| metric.name() == name)
+            .ok_or(format!("Unknown metric: {}, expected one of cpu, memory, temperature, network", name))
+    }
+}
+
+/// A reading of a metric.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    /// Compared with the threshold: percent, °C or KiB/s
+    pub value: f64,
+    /// Shown on the screen, e.g. `42%` or `3.1/15.6 GiB`
+    pub text: String
+}
+
+fn read(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+/// Total and idle jiffies of all CPUs, from the first line of `/proc/stat`.
+fn cpu_times() -> Result<(u64, u64), String> {
+    let stat = read("/proc/stat")?;
+    let times: Vec<u64> = stat.lines().next()
+        .filter(|line| line.starts_with("cpu "))
+        .ok_or("Unexpected format of /proc/stat")?
+        .split_whitespace().skip(1)
+        .filter_map(|time| time.parse().ok())
+        .collect();
+
+    // idle and iowait. The total stops before guest and guest_nice, they are already counted in user and nice
+    let idle = times.get(3).copied().unwrap_or_default() + times.get(4).copied().unwrap_or_default();
+    Ok((times.iter().take(8).sum(), idle))
+}
+
+fn memory() -> Result<Sample, String> {
+    let meminfo = read("/proc/meminfo")?;
+    let field = |name: &str| meminfo.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+        .and_then(|value| value.split_whitespace().next()?.parse::<f64>().ok())
+        .ok_or(format!("{} not found in /proc/meminfo", name));
+
+    let total = field("MemTotal")?;
+    let used = total - field("MemAvailable")?;
+    let percent = used * 100.0 / total.max(1.0);
+    let gib = |kib: f64| kib / 1024.0 / 1024.0;
+
+    Ok(Sample {
+        value: percent,
+        text: format!("{:.0}%  {:.1}/{:.1} GiB", percent, gib(used), gib(total))
+    })
+}
+
+/// Highest temperature of the hwmon sensors, or of the sensor with the given name, e.g. `k10temp`.
+fn temperature(sensor: Option<&str>) -> Result<Sample, String> {
+    let mut highest: Option<f64> = None;
+
+    let entries = std::fs::read_dir("/sys/class/hwmon").map_err(|e| format!("Failed to read /sys/class/hwmon: {}", e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = std::fs::read_to_string(path.join("name")).unwrap_or_default();
+        if sensor.is_some_and(|sensor| sensor != name.trim()) {
+            continue;
+        }
+
+        for input in std::fs::read_dir(&path).into_iter().flatten().flatten() {
+            let file_name = input.file_name();
+            let file_name = file_name.to_string_lossy();
+            if !(file_name.starts_with("temp") && file_name.ends_with("_input")) {
+                continue;
+            }
+
+            let millis = std::fs::read_to_string(input.path()).ok().and_then(|value| value.trim().parse::<f64>().ok());
+            if let Some(celsius) = millis.map(|millis| millis / 1000.0) {
+                highest = Some(highest.map_or(celsius, |highest| highest.max(celsius)));
+            }
+        }
+    }
+
+    let celsius = highest.ok_or(match sensor {
+        Some(sensor) => format!("No temperature found for sensor {}", sensor),
+        None => "No temperature sensors found".to_string()
+    })?;
+
+    Ok(Sample {
+        value: celsius,
+        text: format!("{:.0} C", celsius)
+    })
+}
+
+/// Received and sent bytes of an interface, or of all but loopback, from `/proc/net/dev`.
+fn network_bytes(interface: Option<&str>) -> Result<(u64, u64), String> {
+    let dev = read("/proc/net/dev")?;
+    let mut total = (0, 0);
+
+    // Two header lines, then `name: rx_bytes packets errs drop fifo frame compressed multicast tx_bytes ...`
+    for line in dev.lines().skip(2) {
+        let Some((name, counters)) = line.split_once(':') else { continue };
+        let name = name.trim();
+        if interface.map_or(name == "lo", |interface| interface != name) {
+            continue;
+        }
+
+        let counters: Vec<u64> = counters.split_whitespace().filter_map(|value| value.parse().ok()).collect();
+        total.0 += counters.first().copied().unwrap_or_default();
+        total.1 += counters.get(8).copied().unwrap_or_default();
+    }
+
+    Ok(total)
+}
+
+fn format_rate(kib_per_sec: f64) -> String {
+    if kib_per_sec >= 1024.0 {
+        format!("{:.1}M", kib_per_sec / 1024.0)
+    } else {
+        format!("{:.0}K", kib_per_sec)
+    }
+}
+
+/// Reads a metric and remembers the counters that only make sense as a difference.
+struct Reader {
+    metric: Metric,
+    config: MetricConfig,
+    previous: Option<((u64, u64), Instant)>,
+    next: Instant,
+    /// Cleared while the value is above the threshold, so that it only alerts once
+    armed: bool
+}
+
+impl Reader {
+    fn sample(&mut self) -> Result<Option<Sample>, String> {
+        let now = Instant::now();
+        match self.metric {
+            Metric::Memory => memory().map(Some),
+            Metric::Temperature => temperature(self.config.source.as_deref()).map(Some),
+            Metric::Cpu => {
+                let (total, idle) = cpu_times()?;
+                let previous = self.previous.replace(((total, idle), now));
+                // iowait can go back, such a sample is skipped rather than read as a huge load
+                Ok(previous.and_then(|((last_total, last_idle), _)| {
+                    let total = total.checked_sub(last_total)?;
+                    let busy = total.saturating_sub(idle.checked_sub(last_idle)?);
+                    let load = busy as f64 * 100.0 / total.max(1) as f64;
+                    Some(Sample {
+                        value: load,
+                        text: format!("{:.0}%", load)
+                    })
+                }))
+            },
+            Metric::Network => {
+                let (received, sent) = network_bytes(self.config.source.as_deref())?;
+                let previous = self.previous.replace(((received, sent), now));
+                Ok(previous.map(|((last_received, last_sent), at)| {
+                    let secs = now.duration_since(at).as_secs_f64().max(0.001);
+                    let down = received.saturating_sub(last_received) as f64 / 1024.0 / secs;
+                    let up = sent.saturating_sub(last_sent) as f64 / 1024.0 / secs;
+                    Sample {
+                        value: down + up,
+                        text: format!("D {}  U {}", format_rate(down), format_rate(up))
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// Samples the metrics at their own refresh rates, also while they aren't on the screen,
+/// and queues an alert when one goes above its threshold.
+pub struct MetricsSampler {
+    controller: Arc<Controller>,
+    readers: Vec<Reader>,
+    samples: Arc<Mutex<HashMap<Metric, Sample>>>
+}
+
+impl MetricsSampler {
+    pub fn new(controller: Arc<Controller>, metrics: &HashMap<Metric, MetricConfig>) -> MetricsSampler {
+        MetricsSampler {
+            controller,
+            readers: metrics.iter().map(|(metric, config)| Reader {
+                metric: *metric,
+                config: config.clone(),
+                previous: None,
+                next: Instant::now(),
+                armed: true
+            }).collect(),
+            samples: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    /// The latest samples, read by the providers.
+    pub fn samples(&self) -> Arc<Mutex<HashMap<Metric, Sample>>> {
+        Arc::clone(&self.samples)
+    }
+
+    /// Runs until the task is aborted.
+    pub async fn run(mut self) {
+        loop {
+            let now = Instant::now();
+            for reader in self.readers.iter_mut().filter(|reader| reader.next <= now) {
+                reader.next = now + Duration::from_millis(reader.config.refresh_millis);
+
+                let sample = match reader.sample() {
+                    Ok(Some(sample)) => sample,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        error!("Failed to read {}: {}", reader.metric.name(), e);
+                        continue;
+                    }
+                };
+
+                match reader.config.alert_above {
+                    Some(threshold) if sample.value > threshold && reader.armed => {
+                        reader.armed = false;
+                        self.controller.notify(Notification {
+                            text: format!("{} {}", reader.metric.label(), sample.text),
+                            icon: reader.metric.icon(),
+                            duration: ALERT_DURATION,
+                            priority: ALERT_PRIORITY,
+                            blinks: Some(ALERT_BLINKS),
+                            expires_at: Some(now + ALERT_DURATION)
+                        }).await;
+                    },
+                    Some(threshold) if sample.value <= threshold && !reader.armed => {
+                        info!("{} is back under {}", reader.metric.label(), threshold);
+                        reader.armed = true;
+                    },
+                    _ => {}
+                }
+
+                self.samples.lock().await.insert(reader.metric, sample);
+            }
+
+            let next = self.readers.iter().map(|reader| reader.next).min();
+            match next {
+                Some(next) => tokio::time::sleep_until(next.into()).await,
+                None => return
+            }
+        }
+    }
+}
+
+/// Data of the metric events.
+#[derive(EventPayload)]
+struct MetricUpdate {
+    /// Percent of the max, the length of the progress bar
+    #[payload(value)]
+    level: i32,
+    /// The sample with its unit
+    text: String,
+    sample: f64,
+    /// Empty text after the label
+    nullstr: String
+}
+
+/// Shows one metric with its icon and a progress bar, ready once it has been sampled.
+pub struct MetricProvider {
+    metric: Metric,
+    config: MetricConfig,
+    samples: Arc<Mutex<HashMap<Metric, Sample>>>
+}
+
+impl MetricProvider {
+    pub fn new(metric: Metric, config: MetricConfig, samples: Arc<Mutex<HashMap<Metric, Sample>>>) -> MetricProvider {
+        MetricProvider {
+            metric,
+            config,
+            samples
+        }
+    }
+}
+
+#[async_trait]
+impl ScreenProvider for MetricProvider {
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        Ok(vec![EventBinding::for_payload::<MetricUpdate>(C2SGameEventCreate {
+                game: GAME.to_string(),
+                event: self.metric.event().to_string(),
+                min_value: 0,
+                max_value: 100,
+                icon_id: self.metric.icon(),
+                value_optional: false,
+            }, vec![Screen::new().frame(Screen::multi_line()
+                .line(text().prefix(self.metric.label()).frame_key("nullstr"))
+                .line(text().frame_key("text"))
+                .line(progress_bar())
+                // Outlasts the next refresh, so the icon stays until something else is shown
+                .length_millis(self.config.refresh_millis.saturating_mul(2).min(i32::MAX as u64) as i32)
+                .icon(self.metric.icon())).into()])?])
+    }
+
+    async fn is_ready(&mut self, _now: Instant) -> bool {
+        self.samples.lock().await.contains_key(&self.metric)
+    }
+
+    async fn frame(&mut self, _now: Instant, _fresh: bool) -> Result<Frame, reqwest::Error> {
+        let Some(sample) = self.samples.lock().await.get(&self.metric).cloned() else {
+            return Ok(Frame::Unchanged);
+        };
+
+        let max = self.config.max.unwrap_or(self.metric.default_max());
+        let level = (sample.value * 100.0 / max).clamp(0.0, 100.0);
+
+        Ok(Frame::Event(self.metric.event().to_string(), MetricUpdate {
+            level: level.round() as i32,
+            text: sample.text,
+            sample: sample.value,
+            nullstr: "".to_string()
+        }.to_event_data()))
+    }
+}
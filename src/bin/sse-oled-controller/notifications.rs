@@ -0,0 +1,274 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use sse_oled_controller::api_types::C2SGameEventBind;
+use sse_oled_controller::notify::{self, DEFAULT_NOTIFY_DURATION, DEFAULT_NOTIFY_ICON, NOTIFY};
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::types::Icon;
+use crate::clock::GAME;
+use crate::controller::Controller;
+
+/// How long a notification may wait in the queue before it's dropped, unless told otherwise.
+pub const DEFAULT_NOTIFY_EXPIRY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub text: String,
+    pub icon: Icon,
+    pub duration: Duration,
+    /// Higher priorities are shown first and interrupt lower ones
+    pub priority: u8,
+    /// Blink this many times over `duration` instead of showing still text
+    pub blinks: Option<i32>,
+    /// Dropped if it couldn't be shown before then, never once it has been on the screen
+    pub expires_at: Option<Instant>
+}
+
+impl Notification {
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// Notifications waiting for the screen, and the one on it.
+pub struct NotificationQueue {
+    pending: Vec<Notification>,
+    showing: Option<(Notification, Instant)>
+}
+
+impl NotificationQueue {
+    pub fn new() -> NotificationQueue {
+        NotificationQueue {
+            pending: vec![],
+            showing: None
+        }
+    }
+
+    pub fn push(&mut self, notification: Notification) {
+        self.pending.push(notification);
+    }
+
+    /// Drops the waiting notifications and the one on the screen, returns how many there were.
+    pub fn clear(&mut self) -> usize {
+        let count = self.pending.len() + self.showing.take().map_or(0, |_| 1);
+        self.pending.clear();
+        count
+    }
+
+    /// Whether a notification is on the screen or waiting for it.
+    pub fn is_ready(&self, now: Instant) -> bool {
+        self.showing(now).is_some() || self.pending.iter().any(|notification| !notification.is_expired(now))
+    }
+
+    pub fn showing(&self, now: Instant) -> Option<&Notification> {
+        self.showing_until(now).and(self.showing.as_ref()).map(|(notification, _)| notification)
+    }
+
+    /// When the notification on the screen is done, if there is one.
+    pub fn showing_until(&self, now: Instant) -> Option<Instant> {
+        match &self.showing {
+            Some((notification, since)) if *since + notification.duration > now => Some(*since + notification.duration),
+            _ => None
+        }
+    }
+
+    /// Returns the notification that should be put on the screen now, if any.
+    /// A waiting notification with a higher priority interrupts the one on the screen,
+    /// which goes back to the queue with the time it had left.
+    pub fn poll(&mut self, now: Instant) -> Option<Notification> {
+        self.pending.retain(|notification| !notification.is_expired(now));
+        if self.showing(now).is_none() {
+            self.showing = None;
+        }
+
+        // Highest priority first, the oldest one among equals
+        let (index, priority) = self.pending.iter().enumerate()
+            .rev()
+            .map(|(index, notification)| (index, notification.priority))
+            .max_by_key(|(_, priority)| *priority)?;
+
+        if let Some((current, since)) = &self.showing {
+            if priority <= current.priority {
+                return None;
+            }
+
+            // It already got the screen, so it waits for the rest of its time however long that takes
+            let left = (*since + current.duration).saturating_duration_since(now);
+            self.pending.push(Notification {
+                duration: left,
+                expires_at: None,
+                ..current.clone()
+            });
+        }
+
+        let next = self.pending.remove(index);
+        self.showing = Some((next.clone(), now));
+        Some(next)
+    }
+}
+
+/// Shows the queued notifications, one after another.
+pub struct NotificationProvider {
+    controller: Arc<Controller>
+}
+
+impl NotificationProvider {
+    pub fn new(controller: Arc<Controller>) -> NotificationProvider {
+        NotificationProvider {
+            controller
+        }
+    }
+}
+
+#[async_trait]
+impl ScreenProvider for NotificationProvider {
+    /// `NOTIFY` is bound again with the icon and timing of every notification, this is only the first binding.
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        Ok(vec![EventBinding {
+            event: notify::notify_event(GAME, DEFAULT_NOTIFY_ICON),
+            handlers: notify::notify_handlers(DEFAULT_NOTIFY_ICON, DEFAULT_NOTIFY_DURATION, None)
+        }])
+    }
+
+    async fn is_ready(&mut self, now: Instant) -> bool {
+        self.controller.notifications.lock().await.is_ready(now)
+    }
+
+    async fn frame(&mut self, now: Instant, fresh: bool) -> Result<Frame, reqwest::Error> {
+        let next = {
+            let mut queue = self.controller.notifications.lock().await;
+            // Got the screen back while a notification was showing, put it up again
+            queue.poll(now).or_else(|| queue.showing(now).filter(|_| fresh).cloned())
+        };
+
+        let Some(notification) = next else {
+            return Ok(Frame::Unchanged);
+        };
+
+        let event = notify::notify_event(GAME, notification.icon);
+        self.controller.api.bind_event(C2SGameEventBind {
+            game: event.game,
+            event: event.event,
+            min_value: event.min_value,
+            max_value: event.max_value,
+            icon_id: event.icon_id,
+            handlers: notify::notify_handlers(notification.icon, notification.duration, notification.blinks)
+        }).await?;
+
+        Ok(Frame::Event(NOTIFY.to_string(), notify::notify_data(&notification.text)))
+    }
+
+    /// `clear` drops all notifications and goes back to what was shown before.
+    async fn command(&mut self, args: Vec<String>) -> Result<String, String> {
+        if args != ["clear"] {
+            return Err("Usage: clear".to_string());
+        }
+
+        let count = self.controller.notifications.lock().await.clear();
+        Ok(format!("Cleared {} notifications", count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(text: &str, priority: u8, secs: u64) -> Notification {
+        Notification {
+            text: text.to_string(),
+            icon: DEFAULT_NOTIFY_ICON,
+            duration: Duration::from_secs(secs),
+            priority,
+            blinks: None,
+            expires_at: None
+        }
+    }
+
+    fn text(notification: Option<Notification>) -> Option<String> {
+        notification.map(|notification| notification.text)
+    }
+
+    #[test]
+    fn highest_priority_first_then_oldest() {
+        let start = Instant::now();
+        let mut queue = NotificationQueue::new();
+        queue.push(notification("low", 0, 5));
+        queue.push(notification("first", 3, 5));
+        queue.push(notification("second", 3, 5));
+
+        assert_eq!(text(queue.poll(start)), Some("first".to_string()));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(1))), None);
+        assert_eq!(text(queue.poll(start + Duration::from_secs(5))), Some("second".to_string()));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(10))), Some("low".to_string()));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(15))), None);
+        assert!(!queue.is_ready(start + Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn expired_notifications_are_dropped_while_waiting() {
+        let start = Instant::now();
+        let mut queue = NotificationQueue::new();
+        queue.push(notification("showing", 0, 10));
+        queue.push(Notification {
+            expires_at: Some(start + Duration::from_secs(5)),
+            ..notification("late", 0, 5)
+        });
+
+        assert_eq!(text(queue.poll(start)), Some("showing".to_string()));
+        assert!(queue.is_ready(start + Duration::from_secs(9)));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(10))), None);
+        assert!(!queue.is_ready(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn shown_notifications_do_not_expire() {
+        let start = Instant::now();
+        let mut queue = NotificationQueue::new();
+        queue.push(Notification {
+            expires_at: Some(start + Duration::from_secs(1)),
+            ..notification("shown", 0, 10)
+        });
+
+        assert_eq!(text(queue.poll(start)), Some("shown".to_string()));
+        assert_eq!(queue.showing(start + Duration::from_secs(5)).map(|n| n.text.as_str()), Some("shown"));
+        assert_eq!(queue.showing_until(start), Some(start + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn interrupted_notification_gets_the_rest_of_its_time() {
+        let start = Instant::now();
+        let mut queue = NotificationQueue::new();
+        queue.push(Notification {
+            expires_at: Some(start + Duration::from_secs(1)),
+            ..notification("long", 0, 10)
+        });
+        assert_eq!(text(queue.poll(start)), Some("long".to_string()));
+
+        // Same priority waits, a higher one interrupts
+        queue.push(notification("equal", 0, 2));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(4))), None);
+        queue.push(notification("urgent", 5, 2));
+        assert_eq!(text(queue.poll(start + Duration::from_secs(4))), Some("urgent".to_string()));
+
+        // The interrupted one is queued again behind the older one, and is shown even past its expiry
+        let resumed = queue.poll(start + Duration::from_secs(6)).unwrap();
+        assert_eq!(resumed.text, "equal");
+        let resumed = queue.poll(start + Duration::from_secs(8)).unwrap();
+        assert_eq!(resumed.text, "long");
+        assert_eq!(resumed.duration, Duration::from_secs(6));
+        assert_eq!(resumed.expires_at, None);
+        assert_eq!(queue.showing_until(start + Duration::from_secs(8)), Some(start + Duration::from_secs(14)));
+    }
+
+    #[test]
+    fn clear_drops_everything() {
+        let start = Instant::now();
+        let mut queue = NotificationQueue::new();
+        queue.push(notification("a", 0, 5));
+        queue.push(notification("b", 0, 5));
+        queue.poll(start);
+
+        assert_eq!(queue.clear(), 2);
+        assert!(!queue.is_ready(start));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use log::error;
+use tokio::sync::Mutex;
+use sse_oled_controller::api::SSEngineAPI;
+use sse_oled_controller::scheduler::ScreenHandle;
+use crate::config::ClockConfig;
+use crate::layout::{self, Layout, LayoutRegistry};
+
+const POLL_INTERVAL_MILLIS: u64 = 1000;
+
+/// Polls the config, layout and GoLisp files and applies any change without restarting the game.
+pub struct Reloader {
+    api: Arc<SSEngineAPI>,
+    config: Arc<Mutex<ClockConfig>>,
+    /// Binds the events of the screen providers again once the new config is in place
+    screen: Arc<ScreenHandle>,
+    /// Command line arguments, so that the flags keep overriding the reloaded file
+    args: Vec<String>,
+    registry: LayoutRegistry,
+    modified: HashMap<String, Option<SystemTime>>
+}
+
+impl Reloader {
+    pub fn new(api: Arc<SSEngineAPI>, config: Arc<Mutex<ClockConfig>>, screen: Arc<ScreenHandle>,
+               args: Vec<String>, registry: LayoutRegistry) -> Reloader {
+        Reloader {
+            api,
+            config,
+            screen,
+            args,
+            registry,
+            modified: HashMap::new()
+        }
+    }
+
+    pub async fn run(mut self) {
+        self.poll_changes().await;
+
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(POLL_INTERVAL_MILLIS)).await;
+
+            if self.poll_changes().await {
+                self.reload().await;
+            }
+        }
+    }
+
+    /// Returns whether any watched file has been modified since the last poll.
+    async fn poll_changes(&mut self) -> bool {
+        let config = self.config.lock().await;
+        let paths: Vec<String> = config.source.iter().chain(config.layouts.iter()).chain(config.golisp.iter()).cloned().collect();
+        drop(config);
+
+        let mut changed = false;
+        for path in paths {
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+            if self.modified.insert(path, modified) != Some(modified) {
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    async fn reload(&mut self) {
+        let new_config = match ClockConfig::from_args(self.args.clone()) {
+            Ok(Some(config)) => config,
+            // Only the arguments of the start are read again, and they didn't ask for the help
+            Ok(None) => return,
+            Err(e) => {
+                error!("Failed to reload config, keeping the old one: {}", e);
+                return;
+            }
+        };
+
+        let mut config = self.config.lock().await;
+        *config = new_config;
+        let layouts = config.layouts.clone();
+        let golisp = config.golisp.clone();
+        drop(config);
+        self.screen.rebind();
+
+        match Layout::load_all(&layouts, self.registry.reserved()) {
+            Ok(layout) => {
+                if let Err(e) = self.registry.apply(&self.api, layout).await {
+                    error!("Failed to apply layout changes: {}", e);
+                }
+            },
+            Err(errors) => {
+                error!("Failed to reload layouts, keeping the old ones:");
+                errors.iter().for_each(|e| error!("{}", e));
+            }
+        }
+
+        if golisp.is_empty() {
+            return;
+        }
+        match layout::load_golisp(&golisp) {
+            Ok(golisp) => if let Err(e) = self.api.load_golisp_handlers(golisp).await {
+                error!("Failed to upload GoLisp handlers: {}", e);
+            },
+            Err(e) => error!("Failed to reload GoLisp handlers, keeping the old ones: {}", e)
+        }
+    }
+}
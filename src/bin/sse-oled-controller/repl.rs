@@ -0,0 +1,514 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use log::info;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use sse_oled_controller::control;
+use crate::timers;
+
+/// What the REPL should do after a command has run.
+pub enum Flow {
+    Continue,
+    Exit
+}
+
+pub type Handler<C> = Box<dyn Fn(&C, Args) -> Result<Flow, String> + Send + Sync>;
+
+#[derive(Clone, Copy)]
+pub enum ArgKind {
+    /// Everything left on the line, joined with spaces
+    Rest,
+    /// Everything left on the line, word by word
+    Words,
+    /// The next word
+    Word,
+    /// A duration like `25m` or `1h30m`
+    Duration,
+    /// A time of day like `18:30`, or a date and time, as the time left until then
+    Until,
+    /// One of the listed words
+    Choice(&'static [&'static str])
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool
+}
+
+impl ArgSpec {
+    pub fn required(name: &'static str, kind: ArgKind) -> ArgSpec {
+        ArgSpec { name, kind, optional: false }
+    }
+
+    /// An argument that may be left out, shown as `[name]` in the usage.
+    pub fn optional(name: &'static str, kind: ArgKind) -> ArgSpec {
+        ArgSpec { name, kind, optional: true }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ArgValue {
+    Text(String),
+    Words(Vec<String>),
+    Duration(Duration)
+}
+
+/// Arguments of a command, already parsed to the kinds its `ArgSpec`s ask for.
+pub struct Args {
+    values: BTreeMap<&'static str, ArgValue>
+}
+
+impl Args {
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(ArgValue::Text(text)) => Some(text),
+            _ => None
+        }
+    }
+
+    pub fn words(&self, name: &str) -> Option<&[String]> {
+        match self.values.get(name) {
+            Some(ArgValue::Words(words)) => Some(words),
+            _ => None
+        }
+    }
+
+    pub fn duration(&self, name: &str) -> Option<Duration> {
+        match self.values.get(name) {
+            Some(ArgValue::Duration(duration)) => Some(*duration),
+            _ => None
+        }
+    }
+}
+
+pub struct Command<C> {
+    pub name: &'static str,
+    pub summary: &'static str,
+    /// Longer description shown by `help <command>`
+    pub help: &'static str,
+    pub args: Vec<ArgSpec>,
+    /// Picked when the first argument is the name of a subcommand
+    pub subcommands: Vec<Command<C>>,
+    /// Runs the command itself, `None` when only the subcommands do something
+    pub handler: Option<Handler<C>>
+}
+
+impl<C> Command<C> {
+    pub fn new(name: &'static str, summary: &'static str) -> Command<C> {
+        Command {
+            name,
+            summary,
+            help: "",
+            args: vec![],
+            subcommands: vec![],
+            handler: None
+        }
+    }
+
+    pub fn help(mut self, help: &'static str) -> Command<C> {
+        self.help = help;
+        self
+    }
+
+    pub fn arg(mut self, arg: ArgSpec) -> Command<C> {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn subcommand(mut self, command: Command<C>) -> Command<C> {
+        self.subcommands.push(command);
+        self
+    }
+
+    pub fn handler(mut self, handler: impl Fn(&C, Args) -> Result<Flow, String> + Send + Sync + 'static) -> Command<C> {
+        self.handler = Some(Box::new(handler));
+        self
+    }
+
+    /// One line usage generated from the args and subcommands, e.g. `timer <duration> | timer cancel`.
+    pub fn usage(&self) -> String {
+        let mut usages = vec![];
+
+        if self.handler.is_some() || self.subcommands.is_empty() {
+            let mut usage = self.name.to_string();
+            for arg in &self.args {
+                let name = match arg.kind {
+                    ArgKind::Choice(choices) => choices.join("|"),
+                    _ => arg.name.to_string()
+                };
+                usage += &if arg.optional { format!(" [{}]", name) } else { format!(" <{}>", name) };
+            }
+            usages.push(usage);
+        }
+
+        for sub in &self.subcommands {
+            usages.push(format!("{} {}", self.name, sub.usage()));
+        }
+
+        usages.join(" | ")
+    }
+
+    fn parse_args(&self, words: &[String]) -> Result<Args, String> {
+        let mut values = BTreeMap::new();
+        let mut words = words.iter();
+
+        for spec in &self.args {
+            let value = match spec.kind {
+                ArgKind::Words => {
+                    let rest: Vec<String> = words.by_ref().cloned().collect();
+                    (!rest.is_empty()).then_some(ArgValue::Words(rest))
+                },
+                ArgKind::Rest => rest(&mut words).map(ArgValue::Text),
+                ArgKind::Until => match rest(&mut words) {
+                    Some(text) => Some(ArgValue::Duration(timers::parse_until(&text)?)),
+                    None => None
+                },
+                ArgKind::Word => words.next().cloned().map(ArgValue::Text),
+                ArgKind::Duration => match words.next() {
+                    Some(word) => Some(ArgValue::Duration(control::parse_duration(word)?)),
+                    None => None
+                },
+                ArgKind::Choice(choices) => match words.next() {
+                    Some(word) if !choices.contains(&word.as_str()) => {
+                        return Err(format!("Invalid <{}>: {}, expected one of {}", spec.name, word, choices.join(", ")));
+                    },
+                    word => word.cloned().map(ArgValue::Text)
+                }
+            };
+
+            match value {
+                Some(value) => {
+                    values.insert(spec.name, value);
+                },
+                None if spec.optional => {},
+                None => return Err(format!("Missing <{}>, usage: {}", spec.name, self.usage()))
+            }
+        }
+
+        if let Some(extra) = words.next() {
+            return Err(format!("Unexpected argument: {}, usage: {}", extra, self.usage()));
+        }
+
+        Ok(Args {
+            values
+        })
+    }
+
+    fn run(&self, context: &C, words: &[String]) -> Result<Flow, String> {
+        if let Some(first) = words.first() {
+            if let Some(sub) = self.subcommands.iter().find(|sub| sub.name == first) {
+                return sub.run(context, &words[1..]);
+            }
+        }
+
+        match &self.handler {
+            Some(handler) => handler(context, self.parse_args(words)?),
+            None => Err(format!("Usage: {}", self.usage()))
+        }
+    }
+
+    /// Words that may come next after `words`, for tab completion.
+    fn candidates(&self, words: &[String]) -> Vec<String> {
+        if let Some((first, rest)) = words.split_first() {
+            if let Some(sub) = self.subcommands.iter().find(|sub| sub.name == first) {
+                return sub.candidates(rest);
+            }
+        }
+
+        let mut candidates: Vec<String> = if words.is_empty() {
+            self.subcommands.iter().map(|sub| sub.name.to_string()).collect()
+        } else {
+            vec![]
+        };
+
+        if let Some(ArgSpec { kind: ArgKind::Choice(choices), .. }) = self.args.get(words.len()) {
+            candidates.extend(choices.iter().map(|c| c.to_string()));
+        }
+
+        candidates
+    }
+}
+
+/// Everything left on the line joined with spaces, `None` when nothing is left.
+fn rest<'a>(words: &mut impl Iterator<Item = &'a String>) -> Option<String> {
+    let rest: Vec<&str> = words.map(|w| w.as_str()).collect();
+    if rest.is_empty() { None } else { Some(rest.join(" ")) }
+}
+
+/// All commands known to the REPL, `help` is always available.
+pub struct Commands<C> {
+    commands: Vec<Command<C>>,
+    /// Only parses the arguments of `help`, which is run by `Commands` itself
+    help: Command<C>
+}
+
+impl<C> Commands<C> {
+    pub fn new() -> Commands<C> {
+        Commands {
+            commands: vec![],
+            help: Command::new("help", "Show help of all or one command")
+                .arg(ArgSpec::optional("command", ArgKind::Word))
+        }
+    }
+
+    pub fn add(&mut self, command: Command<C>) -> &mut Commands<C> {
+        self.commands.push(command);
+        self
+    }
+
+    fn find(&self, name: &str) -> Option<&Command<C>> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    fn help(&self, name: Option<&str>) -> Result<Flow, String> {
+        match name {
+            None => {
+                info!("Available Commands:");
+                info!("  {} - {}", self.help.usage(), self.help.summary);
+                for command in &self.commands {
+                    info!("  {} - {}", command.usage(), command.summary);
+                }
+            },
+            Some(name) => {
+                let command = self.find(name).ok_or(format!("Unknown command: {}", name))?;
+                info!("Usage: {}", command.usage());
+                info!("{}", if command.help.is_empty() { command.summary } else { command.help });
+                for sub in &command.subcommands {
+                    info!("  {} {} - {}", command.name, sub.usage(), sub.summary);
+                }
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Parses and runs one line of input.
+    pub fn execute(&self, context: &C, line: &str) -> Result<Flow, String> {
+        let words = split_words(line)?;
+        let Some((name, args)) = words.split_first() else {
+            return Ok(Flow::Continue);
+        };
+
+        if name == "help" {
+            let args = self.help.parse_args(args)?;
+            return self.help(args.text("command"));
+        }
+
+        match self.find(name) {
+            Some(command) => command.run(context, args),
+            None => Err(format!("Unknown command: {}, type 'help' for a list of commands", name))
+        }
+    }
+
+    fn candidates(&self, words: &[String]) -> Vec<String> {
+        match words.split_first() {
+            None => self.commands.iter().map(|c| c.name.to_string()).chain(["help".to_string()]).collect(),
+            Some((name, rest)) if name == "help" && rest.is_empty() => self.commands.iter().map(|c| c.name.to_string()).collect(),
+            Some((name, rest)) => self.find(name).map(|c| c.candidates(rest)).unwrap_or_default()
+        }
+    }
+}
+
+/// Splits a line into words, text in single or double quotes is kept as one word
+/// and a backslash escapes the next character.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                let escaped = chars.next().ok_or("Nothing to escape at the end of the line")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            },
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            },
+            (c, Some(q)) if c == q => quote = None,
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, _) => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+
+    if quote.is_some() {
+        return Err("Unclosed quote".to_string());
+    }
+
+    words.extend(word);
+    Ok(words)
+}
+
+struct CommandHelper<C> {
+    commands: Arc<Commands<C>>
+}
+
+impl<C> Completer for CommandHelper<C> {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let Ok(words) = split_words(&line[..start]) else {
+            return Ok((start, vec![]));
+        };
+
+        let prefix = &line[start..];
+        let candidates = self.commands.candidates(&words).into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+
+        Ok((start, candidates))
+    }
+}
+
+impl<C> Hinter for CommandHelper<C> {
+    type Hint = String;
+}
+
+impl<C> Highlighter for CommandHelper<C> {}
+
+impl<C> Validator for CommandHelper<C> {}
+
+impl<C> Helper for CommandHelper<C> {}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".sse-oled-controller_history"))
+}
+
+/// Reads commands until one of them asks to exit or the input is closed.
+/// This blocks the thread, so it should run on its own one.
+pub fn run<C>(commands: Commands<C>, context: C) {
+    let commands = Arc::new(commands);
+    let mut editor: Editor<CommandHelper<C>, DefaultHistory> = Editor::new().expect("Failed to create line editor");
+    editor.set_helper(Some(CommandHelper {
+        commands: Arc::clone(&commands)
+    }));
+
+    let history = history_path();
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                println!("Failed to read line: {}", e);
+                break;
+            }
+        };
+
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        match commands.execute(&context, &line) {
+            Ok(Flow::Continue) => {},
+            Ok(Flow::Exit) => break,
+            Err(e) => println!("{}", e)
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn split_plain_words() {
+        assert_eq!(split_words("  timer   25m "), Ok(words(&["timer", "25m"])));
+        assert_eq!(split_words(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn split_quotes() {
+        assert_eq!(split_words(r#"set "Half Life" 'it''s'"#), Ok(words(&["set", "Half Life", "its"])));
+        assert_eq!(split_words(r#"say "it's""#), Ok(words(&["say", "it's"])));
+        assert_eq!(split_words(r#"a "" b"#), Ok(words(&["a", "", "b"])));
+        assert_eq!(split_words(r#"a"b c"d"#), Ok(words(&["ab cd"])));
+        assert_eq!(split_words(r#"say "open"#), Err("Unclosed quote".to_string()));
+    }
+
+    #[test]
+    fn split_escapes() {
+        assert_eq!(split_words(r#"a\ b \"c\" "d\"e""#), Ok(words(&["a b", "\"c\"", "d\"e"])));
+        assert_eq!(split_words(r"a\\b"), Ok(words(&[r"a\b"])));
+        assert!(split_words(r"a\").is_err());
+    }
+
+    fn command() -> Command<()> {
+        Command::new("test", "")
+            .arg(ArgSpec::required("duration", ArgKind::Duration))
+            .arg(ArgSpec::required("action", ArgKind::Choice(&["start", "stop"])))
+            .arg(ArgSpec::optional("text", ArgKind::Rest))
+            .handler(|_, _| Ok(Flow::Continue))
+    }
+
+    #[test]
+    fn parse_kinds() {
+        let args = command().parse_args(&words(&["5m", "start", "a", "b"])).unwrap();
+        assert_eq!(args.duration("duration"), Some(Duration::from_secs(300)));
+        assert_eq!(args.text("action"), Some("start"));
+        assert_eq!(args.text("text"), Some("a b"));
+
+        let args = Command::<()>::new("test", "")
+            .arg(ArgSpec::required("first", ArgKind::Word))
+            .arg(ArgSpec::required("rest", ArgKind::Words))
+            .parse_args(&words(&["a", "b", "c d"]))
+            .unwrap();
+        assert_eq!(args.text("first"), Some("a"));
+        assert_eq!(args.words("rest"), Some(&words(&["b", "c d"])[..]));
+    }
+
+    #[test]
+    fn parse_optional() {
+        let args = command().parse_args(&words(&["5m", "stop"])).unwrap();
+        assert_eq!(args.text("text"), None);
+        assert_eq!(command().usage(), "test <duration> <start|stop> [text]");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let command = command();
+        let error = |input: &[&str]| command.parse_args(&words(input)).err().unwrap();
+        assert_eq!(error(&["5m"]), "Missing <action>, usage: test <duration> <start|stop> [text]");
+        assert_eq!(error(&["5m", "lap"]), "Invalid <action>: lap, expected one of start, stop");
+        assert_eq!(error(&["5x", "start"]), "Invalid duration: 5x");
+
+        let single = Command::<()>::new("one", "").arg(ArgSpec::optional("word", ArgKind::Word));
+        assert_eq!(single.parse_args(&words(&["a", "b"])).err().unwrap(), "Unexpected argument: b, usage: one [word]");
+    }
+
+    #[test]
+    fn help_takes_one_optional_command() {
+        let mut commands = Commands::<()>::new();
+        commands.add(command());
+        assert!(commands.execute(&(), "help").is_ok());
+        assert!(commands.execute(&(), "help test").is_ok());
+        assert_eq!(commands.execute(&(), "help nope").err().unwrap(), "Unknown command: nope");
+        assert_eq!(commands.execute(&(), "help a b").err().unwrap(), "Unexpected argument: b, usage: help [command]");
+    }
+}
@@ -0,0 +1,410 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use log::error;
+use chrono::{Local, NaiveDateTime, NaiveTime, TimeZone};
+use sse_oled_controller::api_types::C2SGameEventCreate;
+use sse_oled_controller::notify;
+use sse_oled_controller::payload::EventPayload;
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::screen::{progress_bar, text, Screen};
+use sse_oled_controller::types::{ColorHandler, ColorMode, ColorRange, ColorSpec, ColorZone, Gradient, Icon, Rgb};
+use crate::clock::GAME;
+use crate::controller::Controller;
+
+pub const TIMER_UPDATE: &str = "TIMER_UPDATE";
+pub const STOPWATCH_UPDATE: &str = "STOPWATCH_UPDATE";
+pub const TIMER_ALERT: &str = "TIMER_ALERT";
+/// Lights the F-keys, kept apart from `TIMER_UPDATE` since it doesn't need the screen
+pub const TIMER_KEYS: &str = "TIMER_KEYS";
+
+const ALERT_BLINK_MILLIS: i32 = 500;
+const ALERT_BLINKS: i32 = 5;
+/// How long a stopped stopwatch stays on the screen, so that the clock comes back.
+const STOPPED_LINGER: Duration = Duration::from_secs(10);
+/// The engine turns the lights back after a while without events, so the F-keys are sent again before that
+const KEYS_RESEND: Duration = Duration::from_secs(10);
+
+pub enum Mode {
+    Clock,
+    Timer(Timer),
+    Stopwatch(Stopwatch),
+    Alert(Instant)
+}
+
+/// Both `timer` and `countdown`, they only differ in how the end is given.
+pub struct Timer {
+    pub label: String,
+    pub started: Instant,
+    pub duration: Duration
+}
+
+impl Timer {
+    /// Percent of the timer that has run.
+    pub fn progress(&self) -> i32 {
+        (self.started.elapsed().as_millis() * 100 / self.duration.as_millis().max(1)).min(100) as i32
+    }
+}
+
+#[derive(Default)]
+pub struct Stopwatch {
+    pub running_since: Option<Instant>,
+    /// When it was last stopped, `None` while it runs
+    pub stopped_at: Option<Instant>,
+    pub accumulated: Duration,
+    pub laps: Vec<Duration>
+}
+
+impl Stopwatch {
+    pub fn elapsed(&self) -> Duration {
+        self.accumulated + self.running_since.map(|t| t.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Data of `TIMER_UPDATE`.
+#[derive(EventPayload)]
+struct TimerUpdate {
+    /// Percent of the timer that has run
+    #[payload(value)]
+    progress: i32,
+    label: String,
+    remaining: String
+}
+
+/// Data of `STOPWATCH_UPDATE`.
+#[derive(EventPayload)]
+struct StopwatchUpdate {
+    #[payload(value)]
+    elapsed: String,
+    /// `Stopwatch`, or `Stopwatch (paused)`
+    state: String,
+    /// Number and time of the last lap, `-` before the first one
+    lap: String
+}
+
+/// Data of `TIMER_KEYS`.
+#[derive(EventPayload)]
+struct TimerKeys {
+    /// Percent of the timer that has run, at least 1, 0 turns the keys off
+    #[payload(value)]
+    progress: i32
+}
+
+/// Data of `TIMER_ALERT`.
+#[derive(EventPayload)]
+struct TimerAlert {
+    /// Always 1, the max of the event
+    #[payload(value)]
+    value: i32,
+    label: String,
+    /// Empty text for the blank frame
+    nullstr: String
+}
+
+pub struct Timers {
+    pub mode: Mode
+}
+
+impl Timers {
+    pub fn new() -> Timers {
+        Timers {
+            mode: Mode::Clock
+        }
+    }
+
+    pub fn start_timer(&mut self, label: &str, duration: Duration) {
+        self.mode = Mode::Timer(Timer {
+            label: label.to_string(),
+            started: Instant::now(),
+            duration
+        });
+    }
+
+    pub fn cancel(&mut self) {
+        self.mode = Mode::Clock;
+    }
+
+    /// Short description of the current mode, e.g. `timer 12:34 left`.
+    pub fn describe(&self) -> String {
+        match &self.mode {
+            Mode::Clock => "clock".to_string(),
+            Mode::Timer(timer) => format!("{} {} left", timer.label.to_lowercase(),
+                format_duration(timer.duration.saturating_sub(timer.started.elapsed()))),
+            Mode::Stopwatch(stopwatch) => format!("stopwatch {}{}", format_duration(stopwatch.elapsed()),
+                if stopwatch.running_since.is_some() { "" } else { " (paused)" }),
+            Mode::Alert(_) => "alert".to_string()
+        }
+    }
+
+    /// Runs a stopwatch subcommand, returns the message to print.
+    /// Refused while a timer or countdown runs, since the stopwatch would replace it.
+    pub fn stopwatch(&mut self, action: &str) -> Result<String, String> {
+        if let Mode::Timer(timer) = &self.mode {
+            return Err(format!("{} is running, cancel it first", timer.label));
+        }
+
+        if action == "reset" {
+            self.mode = Mode::Clock;
+            return Ok("Stopwatch reset".to_string());
+        }
+
+        if !matches!(self.mode, Mode::Stopwatch(_)) {
+            if action != "start" {
+                return Err("Stopwatch is not running".to_string());
+            }
+            self.mode = Mode::Stopwatch(Stopwatch::default());
+        }
+
+        let Mode::Stopwatch(stopwatch) = &mut self.mode else { unreachable!() };
+        match action {
+            "start" => {
+                stopwatch.running_since.get_or_insert_with(Instant::now);
+                stopwatch.stopped_at = None;
+                Ok("Stopwatch started".to_string())
+            },
+            "stop" => {
+                if let Some(since) = stopwatch.running_since.take() {
+                    stopwatch.accumulated += since.elapsed();
+                    stopwatch.stopped_at = Some(Instant::now());
+                }
+                Ok(format!("Stopwatch stopped at {}", format_duration(stopwatch.elapsed())))
+            },
+            "lap" => {
+                let elapsed = stopwatch.elapsed();
+                stopwatch.laps.push(elapsed);
+                Ok(format!("Lap {}: {}", stopwatch.laps.len(), format_duration(elapsed)))
+            },
+            _ => Err(format!("Unknown stopwatch action: {}", action))
+        }
+    }
+
+    /// Whether a timer, the stopwatch or a blinking alert needs the screen.
+    /// A stopped stopwatch only keeps it for a while, it shows again once it's started.
+    pub fn is_active(&self) -> bool {
+        match &self.mode {
+            Mode::Clock => false,
+            Mode::Alert(since) => since.elapsed() < alert_length(),
+            Mode::Stopwatch(stopwatch) => match stopwatch.stopped_at {
+                Some(at) => at.elapsed() < STOPPED_LINGER,
+                None => true
+            },
+            Mode::Timer(_) => true
+        }
+    }
+
+    pub fn tick(&mut self) -> Frame {
+        match &self.mode {
+            Mode::Clock => Frame::Unchanged,
+            Mode::Alert(since) => {
+                // Leave the alert blinking
+                if since.elapsed() >= alert_length() {
+                    self.mode = Mode::Clock;
+                }
+                Frame::Unchanged
+            },
+            Mode::Timer(timer) => {
+                let elapsed = timer.started.elapsed();
+                if elapsed >= timer.duration {
+                    let data = TimerAlert {
+                        value: 1,
+                        label: timer.label.clone(),
+                        nullstr: "".to_string()
+                    };
+                    self.mode = Mode::Alert(Instant::now());
+                    return Frame::Event(TIMER_ALERT.to_string(), data.to_event_data());
+                }
+
+                let remaining = timer.duration - elapsed;
+                Frame::Event(TIMER_UPDATE.to_string(), TimerUpdate {
+                    progress: timer.progress(),
+                    label: timer.label.clone(),
+                    remaining: format_duration(remaining)
+                }.to_event_data())
+            },
+            Mode::Stopwatch(stopwatch) => {
+                let state = if stopwatch.running_since.is_some() { "Stopwatch" } else { "Stopwatch (paused)" };
+                let lap = match stopwatch.laps.last() {
+                    Some(lap) => format!("{} {}", stopwatch.laps.len(), format_duration(*lap)),
+                    None => "-".to_string()
+                };
+
+                Frame::Event(STOPWATCH_UPDATE.to_string(), StopwatchUpdate {
+                    elapsed: format_duration(stopwatch.elapsed()),
+                    state: state.to_string(),
+                    lap
+                }.to_event_data())
+            }
+        }
+    }
+}
+
+/// Puts the timers and the stopwatch on the screen while one of them runs.
+/// The F-keys follow the timer also while another provider has the screen.
+pub struct TimersProvider {
+    controller: Arc<Controller>,
+    /// Last `TIMER_KEYS` value and when it was sent, `None` while the keys are off
+    keys: Option<(i32, Instant)>
+}
+
+impl TimersProvider {
+    pub fn new(controller: Arc<Controller>) -> TimersProvider {
+        TimersProvider {
+            controller,
+            keys: None
+        }
+    }
+
+    /// Lights the F-keys while a timer runs and turns them off once it's cancelled or ended.
+    async fn update_keys(&mut self, progress: Option<i32>, now: Instant) {
+        let value = match (progress, self.keys) {
+            (Some(progress), Some((last, at))) if progress.max(1) == last && now.duration_since(at) < KEYS_RESEND => return,
+            (Some(progress), _) => progress.max(1),
+            (None, Some(_)) => 0,
+            (None, None) => return
+        };
+
+        let data = TimerKeys { progress: value }.to_event_data();
+        match self.controller.api.trigger_event(TIMER_KEYS.to_string(), Some(data)).await {
+            Ok(_) => self.keys = (value > 0).then_some((value, now)),
+            Err(e) => error!("Failed to trigger {}: {}", TIMER_KEYS, e)
+        }
+    }
+}
+
+#[async_trait]
+impl ScreenProvider for TimersProvider {
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        bindings()
+    }
+
+    async fn is_ready(&mut self, now: Instant) -> bool {
+        let (active, progress) = {
+            let timers = self.controller.timers.lock().await;
+            let progress = match &timers.mode {
+                Mode::Timer(timer) => Some(timer.progress()),
+                _ => None
+            };
+            (timers.is_active(), progress)
+        };
+
+        self.update_keys(progress, now).await;
+        active
+    }
+
+    async fn frame(&mut self, _now: Instant, _fresh: bool) -> Result<Frame, reqwest::Error> {
+        Ok(self.controller.timers.lock().await.tick())
+    }
+}
+
+fn alert_length() -> Duration {
+    Duration::from_millis((ALERT_BLINK_MILLIS * 2 * ALERT_BLINKS) as u64)
+}
+
+fn event(name: &str, max_value: i32, icon_id: Icon) -> C2SGameEventCreate {
+    C2SGameEventCreate {
+        game: GAME.to_string(),
+        event: name.to_string(),
+        min_value: 0,
+        max_value,
+        icon_id,
+        value_optional: false,
+    }
+}
+
+/// Turns the F-keys from green to red as the timer runs out, and off with 0.
+fn function_keys() -> ColorHandler {
+    ColorHandler {
+        device_type: "keyboard".to_string(),
+        zone: Some(ColorZone::FunctionKeys),
+        custom_zone_keys: None,
+        mode: ColorMode::Color,
+        color: Some(ColorSpec::Ranges(vec![
+            ColorRange {
+                low: 0,
+                high: 0,
+                color: ColorSpec::Static(Rgb::BLACK)
+            },
+            ColorRange {
+                low: 1,
+                high: 100,
+                color: ColorSpec::Gradient {
+                    gradient: Gradient {
+                        zero: Rgb::GREEN,
+                        hundred: Rgb::RED
+                    }
+                }
+            }
+        ])),
+        rate: None,
+        context_frame_key: None
+    }
+}
+
+/// The timer, stopwatch and alert events.
+fn bindings() -> Result<Vec<EventBinding>, String> {
+    let timer = EventBinding::for_payload::<TimerUpdate>(event(TIMER_UPDATE, 100, Icon::Timer), vec![
+        Screen::new().frame(Screen::multi_line()
+            .line(text().frame_key("label"))
+            .line(text().frame_key("remaining"))
+            .line(progress_bar())).into()
+    ])?;
+
+    let keys = EventBinding::for_payload::<TimerKeys>(event(TIMER_KEYS, 100, Icon::Timer), vec![
+        function_keys().into()
+    ])?;
+
+    let stopwatch = EventBinding::for_payload::<StopwatchUpdate>(event(STOPWATCH_UPDATE, 1, Icon::Clock), vec![
+        Screen::new().frame(Screen::multi_line()
+            .line(text().frame_key("state"))
+            .line(text())
+            .line(text().prefix("Lap ").frame_key("lap"))).into()
+    ])?;
+
+    // Two frames, the second one is blank, repeated a few times to make the alert blink
+    let alert = EventBinding::for_payload::<TimerAlert>(event(TIMER_ALERT, 1, Icon::Timer), vec![
+        Screen::new()
+            .frame(Screen::multi_line()
+                .line(text().prefix("Time's up!").frame_key("nullstr").bold())
+                .line(text().frame_key("label"))
+                .length_millis(ALERT_BLINK_MILLIS)
+                .icon(Icon::Timer))
+            .frame(Screen::multi_line()
+                .line(text().frame_key("nullstr"))
+                .length_millis(ALERT_BLINK_MILLIS)
+                .repeat(ALERT_BLINKS)).into(),
+        notify::alert_buzz().into()
+    ])?;
+
+    Ok(vec![timer, keys, stopwatch, alert])
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Parses `HH:MM[:SS]` (the next occurrence of that time) or `YYYY-MM-DD HH:MM` in local time
+/// and returns how long it is from now.
+pub fn parse_until(input: &str) -> Result<Duration, String> {
+    let now = Local::now();
+
+    let target = if let Ok(datetime) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        datetime
+    } else {
+        let time = NaiveTime::parse_from_str(input, "%H:%M:%S")
+            .or_else(|_| NaiveTime::parse_from_str(input, "%H:%M"))
+            .map_err(|_| format!("Invalid time: {}, expected HH:MM[:SS] or YYYY-MM-DD HH:MM", input))?;
+
+        let today = now.date_naive().and_time(time);
+        if today > now.naive_local() { today } else { today + chrono::Duration::days(1) }
+    };
+
+    let target = Local.from_local_datetime(&target).earliest()
+        .ok_or(format!("Invalid local time: {}", input))?;
+
+    (target - now).to_std().map_err(|_| format!("{} is in the past", input))
+}
@@ -0,0 +1,223 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use log::{debug, error, info};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use sse_oled_controller::api_types::C2SGameEventCreate;
+use sse_oled_controller::payload::EventPayload;
+use sse_oled_controller::scheduler::{EventBinding, Frame, ScreenProvider};
+use sse_oled_controller::screen::{text, Screen};
+use sse_oled_controller::types::Icon;
+use crate::clock::GAME;
+use crate::controller::Controller;
+
+pub const PROVIDER: &str = "voice";
+
+/// How long leaving a call stays on the screen.
+const DISCONNECT_LINGER: Duration = Duration::from_secs(5);
+
+/// Longest line a client may send, it is disconnected when a line gets longer.
+const MAX_LINE_BYTES: u64 = 4096;
+
+/// One line sent to the voice socket. Fields that are left out keep their value.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct VoiceUpdate {
+    pub connected: Option<bool>,
+    pub muted: Option<bool>,
+    pub talking: Option<bool>,
+    /// Channel or call name shown under the state, `null` hides it
+    #[serde(default, deserialize_with = "present")]
+    pub channel: Option<Option<String>>
+}
+
+/// Tells a `null` channel apart from a missing one.
+fn present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::deserialize(deserializer).map(Some)
+}
+
+#[derive(Clone, Debug)]
+pub struct VoiceState {
+    pub connected: bool,
+    pub muted: bool,
+    pub talking: bool,
+    pub channel: Option<String>,
+    /// When the call was left
+    pub left: Option<Instant>,
+    /// Clients connected to the socket, the call is only left once the last one is gone
+    pub clients: usize
+}
+
+impl VoiceState {
+    pub fn new() -> VoiceState {
+        VoiceState {
+            connected: false,
+            muted: false,
+            talking: false,
+            channel: None,
+            left: None,
+            clients: 0
+        }
+    }
+
+    fn apply(&mut self, update: VoiceUpdate) {
+        if let Some(connected) = update.connected.filter(|connected| *connected != self.connected) {
+            self.connected = connected;
+            self.left = (!connected).then(Instant::now);
+        }
+        self.muted = update.muted.unwrap_or(self.muted);
+        self.talking = update.talking.unwrap_or(self.talking);
+        if let Some(channel) = update.channel {
+            self.channel = channel;
+        }
+    }
+
+    fn status(&self) -> &'static Status {
+        match (self.connected, self.muted, self.talking) {
+            (false, _, _) => &STATUSES[3],
+            (true, true, _) => &STATUSES[0],
+            (true, false, true) => &STATUSES[1],
+            (true, false, false) => &STATUSES[2]
+        }
+    }
+}
+
+struct Status {
+    event: &'static str,
+    icon: Icon,
+    text: &'static str
+}
+
+/// One event per icon, so that changing state doesn't need a rebind.
+const STATUSES: [Status; 4] = [
+    Status { event: "VOICE_MUTED", icon: Icon::Muted, text: "Mic muted" },
+    Status { event: "VOICE_TALKING", icon: Icon::Talking, text: "Talking" },
+    Status { event: "VOICE_CONNECTED", icon: Icon::Connect, text: "Connected" },
+    Status { event: "VOICE_DISCONNECTED", icon: Icon::Disconnect, text: "Disconnected" }
+];
+
+/// Reads JSON lines from one client until it leaves.
+/// When the last client leaves, that counts as leaving the call.
+async fn handle_client(stream: TcpStream, controller: Arc<Controller>, state: Arc<Mutex<VoiceState>>) -> std::io::Result<()> {
+    state.lock().await.clients += 1;
+    let result = read_updates(stream, &controller, &state).await;
+
+    let mut state = state.lock().await;
+    state.clients -= 1;
+    if state.clients == 0 && state.connected {
+        state.apply(VoiceUpdate { connected: Some(false), ..Default::default() });
+        controller.screen.refresh(PROVIDER);
+    }
+    result
+}
+
+async fn read_updates(stream: TcpStream, controller: &Controller, state: &Mutex<VoiceState>) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let mut line = vec![];
+        if (&mut reader).take(MAX_LINE_BYTES + 1).read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+
+        if line.len() as u64 > MAX_LINE_BYTES {
+            writer.write_all(format!("{{\"error\":\"Lines are limited to {} bytes\"}}\n", MAX_LINE_BYTES).as_bytes()).await?;
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Line too long"));
+        }
+
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<VoiceUpdate>(&line) {
+            Ok(update) => {
+                debug!("Voice update: {:?}", update);
+                state.lock().await.apply(update);
+                controller.screen.refresh(PROVIDER);
+            },
+            Err(e) => {
+                writer.write_all(format!("{{\"error\":{}}}\n", serde_json::Value::from(e.to_string())).as_bytes()).await?;
+            }
+        }
+    }
+}
+
+/// Accepts clients on the voice socket until the task is aborted.
+pub async fn serve(address: SocketAddr, controller: Arc<Controller>, state: Arc<Mutex<VoiceState>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    info!("Voice socket listening on {}", address);
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!("Voice client connected from {}", peer);
+
+        let controller = Arc::clone(&controller);
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, controller, state).await {
+                error!("Voice client {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Data of the voice events.
+#[derive(EventPayload)]
+struct VoiceFrame {
+    status: String,
+    /// Empty while the channel isn't known
+    channel: String
+}
+
+/// Shows whether the mic is muted or talking while in a call, and leaving the call for a moment.
+pub struct VoiceProvider {
+    state: Arc<Mutex<VoiceState>>
+}
+
+impl VoiceProvider {
+    pub fn new(state: Arc<Mutex<VoiceState>>) -> VoiceProvider {
+        VoiceProvider {
+            state
+        }
+    }
+}
+
+#[async_trait]
+impl ScreenProvider for VoiceProvider {
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        STATUSES.iter().map(|status| EventBinding::for_payload::<VoiceFrame>(C2SGameEventCreate {
+                game: GAME.to_string(),
+                event: status.event.to_string(),
+                min_value: 0,
+                max_value: 100,
+                icon_id: status.icon,
+                value_optional: true,
+            }, vec![Screen::new().frame(Screen::multi_line()
+                .line(text().frame_key("status"))
+                .line(text().frame_key("channel"))
+                // Outlasts the next refresh, so the icon stays until something else is shown
+                .length_millis(3000)
+                .icon(status.icon)).into()])).collect()
+    }
+
+    async fn is_ready(&mut self, now: Instant) -> bool {
+        let state = self.state.lock().await;
+        state.connected || state.left.is_some_and(|left| now.duration_since(left) < DISCONNECT_LINGER)
+    }
+
+    async fn frame(&mut self, _now: Instant, _fresh: bool) -> Result<Frame, reqwest::Error> {
+        let state = self.state.lock().await;
+        let status = state.status();
+
+        Ok(Frame::Event(status.event.to_string(), VoiceFrame {
+            status: status.text.to_string(),
+            channel: state.channel.clone().unwrap_or_default()
+        }.to_event_data()))
+    }
+}
@@ -0,0 +1,282 @@
+use std::time::Duration;
+use log::debug;
+use sse_oled_controller::api::SSEngineAPI;
+use sse_oled_controller::api_types::{C2SGameCreate, EventData, EventValue};
+use sse_oled_controller::control::{self, ControlRequest, ControlResponse, DEFAULT_CONTROL_ADDRESS};
+use sse_oled_controller::notify::{self, DEFAULT_NOTIFY_DURATION, DEFAULT_NOTIFY_ICON, NOTIFY};
+use sse_oled_controller::types::Icon;
+
+const USAGE: &str = "Usage: sse-oledctl [options] <command> [args...]
+Options:
+  --control <address>     Address of the running controller, 127.0.0.1:51290 by default
+  --server <address>      Engine address used when no controller is running
+Commands:
+  set <text>                                   Show a text where the game name goes
+  idle                                         Show IDLE where the game name goes
+  notify [--icon <name>] [--for <duration>] [--priority <0-255>] [--blink <count>] [--expire <duration>] [--] <text>
+                                               Show a message for a while, options go before the text
+  context <key> [value]                        Set or remove a context value of the clock
+  trigger <event> [value]                      Trigger an event of the controller's game
+  timer <duration> | countdown <HH:MM> | stopwatch <action> | clock
+                                               Switch the mode of the controller
+  screen <provider> <command...>               Send a command to a screen provider, e.g. screen clock zone Berlin
+  status                                       Show what the controller is doing";
+
+/// Game used when talking to the engine directly.
+const GAME: &str = "OLED_CTL";
+
+struct Options {
+    control: String,
+    server: Option<String>,
+    command: String,
+    args: Vec<String>
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut control = DEFAULT_CONTROL_ADDRESS.to_string();
+    let mut server = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--control" => control = args.next().ok_or("Missing value for --control")?,
+            "--server" => server = Some(args.next().ok_or("Missing value for --server")?),
+            "--help" | "help" => return Err(USAGE.to_string()),
+            _ => return Ok(Options {
+                control,
+                server,
+                command: arg,
+                args: args.collect()
+            })
+        }
+    }
+
+    Err(USAGE.to_string())
+}
+
+/// A notification, also used for `set` when no controller is running.
+struct Notification {
+    text: String,
+    icon: Icon,
+    duration: Duration,
+    priority: Option<u8>,
+    blinks: Option<i32>,
+    expires: Option<Duration>
+}
+
+fn parse_notification(args: Vec<String>) -> Result<Notification, String> {
+    let mut icon = DEFAULT_NOTIFY_ICON;
+    let mut duration = DEFAULT_NOTIFY_DURATION;
+    let mut priority = None;
+    let mut blinks = None;
+    let mut expires = None;
+    let mut text = vec![];
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--icon" => icon = args.next().ok_or("Missing value for --icon")?.parse()?,
+            "--for" => duration = control::parse_duration(&args.next().ok_or("Missing value for --for")?)?,
+            "--priority" => priority = Some(args.next().ok_or("Missing value for --priority")?
+                .parse().map_err(|_| "Invalid value for --priority")?),
+            "--blink" => blinks = Some(args.next().ok_or("Missing value for --blink")?
+                .parse().map_err(|_| "Invalid value for --blink")?),
+            "--expire" => expires = Some(control::parse_duration(&args.next().ok_or("Missing value for --expire")?)?),
+            "--" => {
+                text.extend(args);
+                break;
+            },
+            _ => {
+                // The text starts here, so it may contain words like --for
+                text.push(arg);
+                text.extend(args);
+                break;
+            }
+        }
+    }
+
+    if text.is_empty() {
+        return Err("No text provided".to_string());
+    }
+
+    Ok(Notification {
+        text: text.join(" "),
+        icon,
+        duration,
+        priority,
+        blinks,
+        expires
+    })
+}
+
+/// Numbers, `true`, `false`, arrays and objects are read as JSON, anything else is a string.
+fn parse_value(value: &str) -> EventValue {
+    serde_json::from_str(value).unwrap_or_else(|_| EventValue::String(value.to_string()))
+}
+
+fn to_request(command: &str, args: Vec<String>) -> Result<ControlRequest, String> {
+    let first = args.first().cloned().ok_or(format!("Missing argument for {}\n{}", command, USAGE));
+
+    Ok(match command {
+        "set" => ControlRequest::SetGame { name: first.and(Ok(args.join(" ")))? },
+        "idle" => ControlRequest::Idle,
+        "notify" => {
+            let notification = parse_notification(args)?;
+            ControlRequest::Notify {
+                text: notification.text,
+                icon: Some(notification.icon.into()),
+                duration: Some(notification.duration.into()),
+                priority: notification.priority,
+                blinks: notification.blinks,
+                expires: notification.expires.map(Into::into)
+            }
+        },
+        "context" => ControlRequest::SetContext {
+            key: first?,
+            value: args.get(1..).filter(|rest| !rest.is_empty()).map(|rest| parse_value(&rest.join(" ")))
+        },
+        "trigger" => ControlRequest::Trigger {
+            game: None,
+            event: first?,
+            data: args.get(1).map(|value| EventData {
+                value: parse_value(value),
+                frame: None
+            })
+        },
+        "timer" => ControlRequest::Timer { duration: first? },
+        "countdown" => ControlRequest::Countdown { until: first.and(Ok(args.join(" ")))? },
+        "stopwatch" => ControlRequest::Stopwatch { action: first? },
+        "screen" => ControlRequest::Screen {
+            provider: first?,
+            args: args[1..].to_vec()
+        },
+        "clock" => ControlRequest::Clock,
+        "status" => ControlRequest::Status,
+        _ => return Err(format!("Unknown command: {}\n{}", command, USAGE))
+    })
+}
+
+async fn send(control: &str, request: &ControlRequest) -> Result<ControlResponse, reqwest::Error> {
+    reqwest::Client::new()
+        .post(format!("http://{}/command", control))
+        .json(request)
+        .send()
+        .await?
+        .json()
+        .await
+}
+
+/// Shows the notification through the engine, for when no controller is running,
+/// and removes the game again once it has been shown.
+async fn notify_directly(server: Option<&str>, notification: Notification) -> Result<(), reqwest::Error> {
+    let mut api = SSEngineAPI::new(server);
+    api.setup(C2SGameCreate {
+        game: GAME.to_string(),
+        game_display_name: Some("OLED Control".to_string()),
+        developer: Some("lamadaemon".to_string()),
+        // The engine drops the game and clears the screen after this long without a heartbeat
+        deinitialize_timer_length_ms: Some(notification.duration.as_millis().clamp(1000, 60000) as u16),
+    }).await?;
+
+    api.new_event_and_bind(notify::notify_event(GAME, notification.icon),
+        notify::notify_handlers(notification.icon, notification.duration, notification.blinks)).await?;
+    api.trigger_event(NOTIFY.to_string(), Some(notify::notify_data(&notification.text))).await?;
+
+    // The heartbeat keeps the game alive until the notification is over
+    tokio::time::sleep(notification.duration).await;
+    api.done().await
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let options = parse_options().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    let request = to_request(&options.command, options.args.clone()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    match send(&options.control, &request).await {
+        Ok(response) => {
+            if let Some(error) = response.error {
+                eprintln!("Error: {}", error);
+                std::process::exit(1);
+            }
+
+            if let Some(message) = response.message {
+                println!("{}", message);
+            }
+
+            if let Some(status) = response.status {
+                println!("Game: {}", status.game);
+                println!("Mode: {}", status.mode);
+                if let Some(screen) = status.screen {
+                    println!("Screen: {}", screen);
+                }
+                for (key, value) in status.context {
+                    println!("Context {}: {:?}", key, value);
+                }
+            }
+        },
+        Err(e) if e.is_connect() => {
+            debug!("Controller is not running: {:?}", e);
+
+            let notification = match options.command.as_str() {
+                "set" => Notification {
+                    text: options.args.join(" "),
+                    icon: DEFAULT_NOTIFY_ICON,
+                    duration: Duration::from_secs(15),
+                    priority: None,
+                    blinks: None,
+                    expires: None
+                },
+                "notify" => parse_notification(options.args).expect("Arguments are parsed before sending"),
+                "status" => {
+                    println!("Controller is not running at {}", options.control);
+                    std::process::exit(1);
+                },
+                _ => {
+                    eprintln!("Controller is not running at {}, '{}' needs it", options.control, options.command);
+                    std::process::exit(1);
+                }
+            };
+
+            if let Err(e) = notify_directly(options.server.as_deref(), notification).await {
+                eprintln!("Failed to reach the engine: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Err(e) => {
+            eprintln!("Failed to talk to the controller: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(args: &[&str]) -> Result<String, String> {
+        parse_notification(args.iter().map(|a| a.to_string()).collect()).map(|n| n.text)
+    }
+
+    #[test]
+    fn options_stop_at_the_text() {
+        let notification = parse_notification(vec!["--for".into(), "5s".into(), "Deploy".into(), "--for".into(), "2s".into()]).unwrap();
+        assert_eq!(notification.duration, Duration::from_secs(5));
+        assert_eq!(notification.text, "Deploy --for 2s");
+    }
+
+    #[test]
+    fn double_dash_starts_the_text() {
+        assert_eq!(text(&["--", "--icon", "x"]), Ok("--icon x".to_string()));
+        assert_eq!(text(&["--blink", "2", "--", "--"]), Ok("--".to_string()));
+        assert_eq!(text(&["--"]), Err("No text provided".to_string()));
+    }
+}
@@ -0,0 +1,175 @@
+//! Per-key lighting of a whole keyboard, sent as one frame of 132 colors.
+//!
+//! The keys are laid out on a grid of 22 columns and 6 rows, in the order of the array.
+//! [`KeyboardBitmap::key`] finds the keys of a standard full size layout by name,
+//! keyboards with another layout may light a different key at some positions.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::api_types::EventData;
+use crate::types::{ColorHandler, ColorMode, Rgb};
+
+pub const WIDTH: usize = 22;
+pub const HEIGHT: usize = 6;
+pub const SIZE: usize = WIDTH * HEIGHT;
+
+/// Key of the context frame the engine reads the bitmap from.
+pub const FRAME_KEY: &str = "bitmap";
+
+/// Names of the keys by row, with their column. Columns that aren't listed have no key.
+const LAYOUT: [&[(&str, usize)]; HEIGHT] = [
+    &[("esc", 0), ("f1", 2), ("f2", 3), ("f3", 4), ("f4", 5), ("f5", 6), ("f6", 7), ("f7", 8), ("f8", 9),
+        ("f9", 10), ("f10", 11), ("f11", 12), ("f12", 13), ("printscreen", 15), ("scrolllock", 16), ("pause", 17)],
+    &[("`", 0), ("1", 1), ("2", 2), ("3", 3), ("4", 4), ("5", 5), ("6", 6), ("7", 7), ("8", 8), ("9", 9), ("0", 10),
+        ("-", 11), ("=", 12), ("backspace", 13), ("insert", 15), ("home", 16), ("pageup", 17),
+        ("numlock", 18), ("kp/", 19), ("kp*", 20), ("kp-", 21)],
+    &[("tab", 0), ("q", 1), ("w", 2), ("e", 3), ("r", 4), ("t", 5), ("y", 6), ("u", 7), ("i", 8), ("o", 9), ("p", 10),
+        ("[", 11), ("]", 12), ("\\", 13), ("delete", 15), ("end", 16), ("pagedown", 17),
+        ("kp7", 18), ("kp8", 19), ("kp9", 20), ("kp+", 21)],
+    &[("capslock", 0), ("a", 1), ("s", 2), ("d", 3), ("f", 4), ("g", 5), ("h", 6), ("j", 7), ("k", 8), ("l", 9),
+        (";", 10), ("'", 11), ("enter", 13), ("kp4", 18), ("kp5", 19), ("kp6", 20)],
+    &[("lshift", 0), ("z", 2), ("x", 3), ("c", 4), ("v", 5), ("b", 6), ("n", 7), ("m", 8), (",", 9), (".", 10),
+        ("/", 11), ("rshift", 13), ("up", 16), ("kp1", 18), ("kp2", 19), ("kp3", 20), ("kpenter", 21)],
+    &[("lctrl", 0), ("lwin", 1), ("lalt", 2), ("space", 6), ("ralt", 10), ("fn", 11), ("menu", 12), ("rctrl", 13),
+        ("left", 15), ("down", 16), ("right", 17), ("kp0", 18), ("kp.", 20)]
+];
+
+/// The color of every key, black by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyboardBitmap {
+    keys: [Rgb; SIZE]
+}
+
+impl Default for KeyboardBitmap {
+    fn default() -> Self {
+        KeyboardBitmap::filled(Rgb::new(0, 0, 0))
+    }
+}
+
+impl KeyboardBitmap {
+    pub fn filled(color: Rgb) -> KeyboardBitmap {
+        KeyboardBitmap {
+            keys: [color; SIZE]
+        }
+    }
+
+    /// Index of a key in the bitmap, e.g. `esc`, `f1`, `w`, `space` or `kp5`. Names are compared without case.
+    pub fn key(name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        LAYOUT.iter().enumerate().find_map(|(row, keys)| {
+            keys.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, column)| row * WIDTH + column)
+        })
+    }
+
+    /// Names of the keys of the layout with their index.
+    pub fn keys() -> HashMap<&'static str, usize> {
+        LAYOUT.iter().enumerate()
+            .flat_map(|(row, keys)| keys.iter().map(move |(key, column)| (*key, row * WIDTH + column)))
+            .collect()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Rgb> {
+        (x < WIDTH && y < HEIGHT).then(|| self.keys[y * WIDTH + x])
+    }
+
+    /// Sets the color at a position, positions outside of the grid are ignored.
+    pub fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < WIDTH && y < HEIGHT {
+            self.keys[y * WIDTH + x] = color;
+        }
+    }
+
+    pub fn set_key(&mut self, name: &str, color: Rgb) -> Result<(), String> {
+        let index = KeyboardBitmap::key(name).ok_or(format!("Unknown key: {}", name))?;
+        self.keys[index] = color;
+        Ok(())
+    }
+
+    pub fn fill(&mut self, color: Rgb) {
+        self.keys = [color; SIZE];
+    }
+
+    /// Fills the rectangle from `(x, y)` that is `width` columns wide and `height` rows high, cut at the edges.
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for row in y..(y + height).min(HEIGHT) {
+            for column in x..(x + width).min(WIDTH) {
+                self.keys[row * WIDTH + column] = color;
+            }
+        }
+    }
+
+    pub fn fill_row(&mut self, y: usize, color: Rgb) {
+        self.fill_rect(0, y, WIDTH, 1, color);
+    }
+
+    pub fn fill_column(&mut self, x: usize, color: Rgb) {
+        self.fill_rect(x, 0, 1, HEIGHT, color);
+    }
+
+    /// Moves every column `columns` to the right, wrapping around, for scrolling animations.
+    /// Negative values move to the left.
+    pub fn shifted(&self, columns: isize) -> KeyboardBitmap {
+        let mut shifted = self.clone();
+        let offset = columns.rem_euclid(WIDTH as isize) as usize;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                shifted.keys[y * WIDTH + (x + offset) % WIDTH] = self.keys[y * WIDTH + x];
+            }
+        }
+        shifted
+    }
+
+    /// Fades towards `other`, from this bitmap at 0 to `other` at 1, for transitions.
+    pub fn blend(&self, other: &KeyboardBitmap, amount: f64) -> KeyboardBitmap {
+        let amount = amount.clamp(0.0, 1.0);
+        let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * amount).round() as u8;
+
+        let mut blended = self.clone();
+        for (key, (from, to)) in blended.keys.iter_mut().zip(self.keys.iter().zip(other.keys.iter())) {
+            *key = Rgb::new(mix(from.red, to.red), mix(from.green, to.green), mix(from.blue, to.blue));
+        }
+        blended
+    }
+
+    /// Event data that shows the bitmap with `bitmap_handler`.
+    pub fn to_event_data(&self) -> EventData {
+        EventData::new(0).with(FRAME_KEY, self.clone())
+    }
+}
+
+/// Sent as an array of `[red, green, blue]` arrays.
+impl Serialize for KeyboardBitmap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.keys.iter().map(|key| [key.red, key.green, key.blue]))
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyboardBitmap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys: Vec<[u8; 3]> = Vec::deserialize(deserializer)?;
+        if keys.len() != SIZE {
+            return Err(serde::de::Error::invalid_length(keys.len(), &"132 colors"));
+        }
+
+        let mut bitmap = KeyboardBitmap::default();
+        for (key, [red, green, blue]) in bitmap.keys.iter_mut().zip(keys) {
+            *key = Rgb::new(red, green, blue);
+        }
+        Ok(bitmap)
+    }
+}
+
+/// Lights the whole keyboard from the bitmap in the context frame.
+pub fn bitmap_handler() -> ColorHandler {
+    ColorHandler {
+        device_type: "rgb-per-key-zones".to_string(),
+        zone: None,
+        custom_zone_keys: None,
+        mode: ColorMode::Bitmap,
+        color: None,
+        rate: None,
+        context_frame_key: None
+    }
+}
@@ -0,0 +1,217 @@
+//! Messages of the control endpoint of the running controller.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::api_types::{EventData, EventValue};
+use crate::types::Icon;
+
+/// Default address of the control endpoint, only reachable from this machine.
+pub const DEFAULT_CONTROL_ADDRESS: &str = "127.0.0.1:51290";
+
+/// A command sent to the running controller, as the JSON body of `POST /command`.
+///
+/// ```json
+/// { "command": "set_context", "key": "status", "value": "Build OK" }
+/// ```
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    /// Show a name where the game name goes
+    SetGame { name: String },
+    Idle,
+    /// Add a value to the context frame sent with the clock, `null` removes it
+    SetContext { key: String, value: Option<EventValue> },
+    /// Trigger any registered event of the controller's game, or of the game of a layout
+    Trigger { game: Option<String>, event: String, data: Option<EventData> },
+    Timer { duration: String },
+    Countdown { until: String },
+    Stopwatch { action: String },
+    /// Show a message for a while, then go back to what was shown before
+    Notify {
+        text: String,
+        icon: Option<IconSpec>,
+        /// How long to show it, e.g. `5s`
+        duration: Option<DurationSpec>,
+        /// Higher priorities are shown first and interrupt lower ones, 0 by default
+        priority: Option<u8>,
+        /// Blink this many times instead of showing still text
+        blinks: Option<i32>,
+        /// Drop it if it couldn't be shown within this time, e.g. `1m`
+        expires: Option<DurationSpec>
+    },
+    /// Send a command to a screen provider, e.g. `clock` with `["zone", "Berlin"]`
+    Screen { provider: String, args: Vec<String> },
+    /// Stop any timer and go back to the clock
+    Clock,
+    Status
+}
+
+/// An icon by its id, or by the name of its variant, e.g. `Lightning`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum IconSpec {
+    Id(Icon),
+    Name(String)
+}
+
+impl IconSpec {
+    pub fn icon(&self) -> Result<Icon, String> {
+        match self {
+            IconSpec::Id(icon) => Ok(*icon),
+            IconSpec::Name(name) => name.parse()
+        }
+    }
+}
+
+impl From<Icon> for IconSpec {
+    fn from(icon: Icon) -> Self {
+        IconSpec::Id(icon)
+    }
+}
+
+/// A duration in milliseconds, or as text like `5s`, see `parse_duration`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DurationSpec {
+    Millis(u64),
+    Text(String)
+}
+
+impl DurationSpec {
+    pub fn duration(&self) -> Result<Duration, String> {
+        match self {
+            DurationSpec::Millis(millis) => Ok(Duration::from_millis(*millis)),
+            DurationSpec::Text(text) => parse_duration(text)
+        }
+    }
+}
+
+impl From<Duration> for DurationSpec {
+    fn from(duration: Duration) -> Self {
+        DurationSpec::Millis(duration.as_millis().min(u64::MAX as u128) as u64)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ControlResponse {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What a provider answered to a `screen` command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<ControlStatus>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ControlStatus {
+    pub game: String,
+    /// What the clock is showing, e.g. `clock` or `timer 12:34`
+    pub mode: String,
+    pub context: HashMap<String, EventValue>,
+    /// Name of the provider on the screen, e.g. `clock` or `notifications`
+    #[serde(default)]
+    pub screen: Option<String>
+}
+
+impl ControlResponse {
+    pub fn ok(status: ControlStatus) -> ControlResponse {
+        ControlResponse {
+            ok: true,
+            error: None,
+            message: None,
+            status: Some(status)
+        }
+    }
+
+    pub fn error(error: impl Into<String>) -> ControlResponse {
+        ControlResponse {
+            ok: false,
+            error: Some(error.into()),
+            message: None,
+            status: None
+        }
+    }
+}
+
+/// Parses durations like `25m`, `1h30m` or `90s`. A bare number is taken as minutes.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", input);
+
+    let secs = match input.parse::<u64>() {
+        Ok(minutes) => minutes.checked_mul(60).ok_or_else(invalid)?,
+        Err(_) => {
+            let mut secs: u64 = 0;
+            let mut number = String::new();
+            for c in input.chars() {
+                if c.is_ascii_digit() {
+                    number.push(c);
+                    continue;
+                }
+
+                let value: u64 = number.parse().map_err(|_| invalid())?;
+                let unit = match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    's' => 1,
+                    _ => return Err(invalid())
+                };
+                secs = value.checked_mul(unit).and_then(|value| secs.checked_add(value)).ok_or_else(invalid)?;
+                number.clear();
+            }
+
+            if !number.is_empty() {
+                return Err(invalid());
+            }
+            secs
+        }
+    };
+
+    // Zero is refused the same way with and without a unit
+    if secs == 0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("25m"), Ok(Duration::from_secs(25 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("1h0m5s"), Ok(Duration::from_secs(3605)));
+    }
+
+    #[test]
+    fn bare_number_is_minutes() {
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn zero_is_refused() {
+        for input in ["0", "0s", "0h0m", "00"] {
+            assert_eq!(parse_duration(input), Err(format!("Invalid duration: {}", input)));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        for input in ["", "m", "5x", "1h30", "-5m", "1.5h", "5 m"] {
+            assert!(parse_duration(input).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(parse_duration(&u64::MAX.to_string()).is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_duration(&format!("{}s{}s", u64::MAX, 1)).is_err());
+    }
+}
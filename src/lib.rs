@@ -0,0 +1,54 @@
+//! Client for the GameSense API of SteelSeries Engine 3, focused on the OLED screens.
+//!
+//! [`api::SSEngineAPI`] registers a game, keeps it alive with heartbeats and sends its events,
+//! [`api_types`] has the request bodies and [`types`] the screen, tactile and color handlers bound to the events,
+//! that [`screen`] builds, with [`arg`] expressions that compute what a line shows.
+//! [`notify`] builds a ready made single line notification, [`bitmap`] lights every key of a keyboard,
+//! [`payload`] checks the handlers of an event against the Rust type of its data,
+//! and [`control`] has the messages of the control endpoint of the `sse-oled-controller` binary.
+//! With the `controller` feature, `scheduler` shares the screen between the providers of that binary.
+//!
+//! ```no_run
+//! use sse_oled_controller::api::SSEngineAPI;
+//! use sse_oled_controller::api_types::C2SGameCreate;
+//! use sse_oled_controller::notify::{self, NOTIFY, DEFAULT_NOTIFY_DURATION};
+//! use sse_oled_controller::types::Icon;
+//!
+//! # async fn run() -> Result<(), reqwest::Error> {
+//! // The address is read from coreProps.json when not given
+//! let mut api = SSEngineAPI::new(None);
+//! api.setup(C2SGameCreate {
+//!     game: "MY_TOOL".to_string(),
+//!     game_display_name: Some("My Tool".to_string()),
+//!     developer: None,
+//!     deinitialize_timer_length_ms: None
+//! }).await?;
+//!
+//! api.new_event_and_bind(notify::notify_event("MY_TOOL", Icon::Lightning),
+//!     notify::notify_handlers(Icon::Lightning, DEFAULT_NOTIFY_DURATION, None)).await?;
+//! api.trigger_event(NOTIFY.to_string(), Some(notify::notify_data("Build done"))).await?;
+//! api.done().await
+//! # }
+//! ```
+//!
+//! # Features
+//!
+//! The library itself only needs `reqwest`, `tokio` and `serde`. The default features build the binaries:
+//!
+//! - `derive` - `#[derive(EventPayload)]`, see [`payload`]
+//! - `ctl` - `sse-oledctl`, the command line client of the control endpoint
+//! - `controller` - `sse-oled-controller`, the clock with its REPL, control endpoint and screen providers
+//!
+//! Depend on the crate with `default-features = false` to only get the client.
+
+pub mod api;
+pub mod api_types;
+pub mod types;
+pub mod screen;
+pub mod arg;
+pub mod control;
+pub mod notify;
+pub mod bitmap;
+pub mod payload;
+#[cfg(feature = "controller")]
+pub mod scheduler;
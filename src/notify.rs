@@ -0,0 +1,104 @@
+//! A notification shown as a single line of text, used by `sse-oledctl` and the controller.
+
+use std::time::Duration;
+use crate::api_types::{C2SGameEventCreate, EventData, EventValue};
+use crate::types::{FrameModifiersData, Handler, Icon, LineContent, Repeat, ScreenData, ScreenFrameData, ScreenHandler, SingleLineFrameData, TactileEffect, TactileHandler, TactilePattern, TextModifierData};
+
+pub const NOTIFY: &str = "NOTIFY";
+
+/// Default time a notification stays on the screen.
+pub const DEFAULT_NOTIFY_DURATION: Duration = Duration::from_secs(5);
+
+/// Icon of a notification when none is given.
+pub const DEFAULT_NOTIFY_ICON: Icon = Icon::NoIcon;
+
+/// The `NOTIFY` event of a game.
+pub fn notify_event(game: &str, icon_id: Icon) -> C2SGameEventCreate {
+    C2SGameEventCreate {
+        game: game.to_string(),
+        event: NOTIFY.to_string(),
+        min_value: 0,
+        max_value: 1,
+        icon_id,
+        value_optional: false,
+    }
+}
+
+fn text_frame(prefix: &str, with_value: bool, frame_modifiers_data: FrameModifiersData) -> ScreenData {
+    ScreenData::FrameData(ScreenFrameData::SingleLine(SingleLineFrameData {
+        content: LineContent::Text(TextModifierData {
+            has_text: with_value,
+            prefix: prefix.to_string(),
+            suffix: "".to_string(),
+            bold: false,
+            wrap: 1,
+        }),
+        frame_modifiers_data: Some(frame_modifiers_data),
+        data_accessor_data: None
+    }))
+}
+
+/// A single line showing the event value with the icon for `duration`.
+/// With `blinks`, the text and a blank frame take turns that many times within `duration`.
+/// The icon and length are part of the binding, so it is rebound for every notification.
+pub fn notify_handler(icon_id: Icon, duration: Duration, blinks: Option<i32>) -> ScreenHandler {
+    let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+
+    let datas = match blinks {
+        Some(blinks) if blinks > 0 => {
+            let half = (millis / blinks / 2).max(1);
+            vec![
+                text_frame("", true, FrameModifiersData {
+                    length_millis: half,
+                    icon_id,
+                    repeats: Repeat::Infinite(false)
+                }),
+                text_frame(" ", false, FrameModifiersData {
+                    length_millis: half,
+                    icon_id: Icon::NoIcon,
+                    repeats: Repeat::Counts(blinks)
+                })
+            ]
+        },
+        _ => vec![text_frame("", true, FrameModifiersData {
+            length_millis: millis,
+            icon_id,
+            repeats: Repeat::Infinite(false)
+        })]
+    };
+
+    ScreenHandler {
+        device_type: "screened".to_string(),
+        zone: "one".to_string(),    // Fixed value
+        mode: "screen".to_string(), // Fixed value
+        datas,
+    }
+}
+
+/// A short buzz of the mouse, for alerts that shouldn't go unnoticed.
+pub fn alert_buzz() -> TactileHandler {
+    TactileHandler {
+        device_type: TactileHandler::default_device_type(),
+        zone: TactileHandler::default_zone(),
+        mode: TactileHandler::default_mode(),
+        pattern: TactilePattern::Steps(vec![TactileEffect::BuzzAlert750ms.step()]),
+        rate: None
+    }
+}
+
+/// The handlers of a notification: `notify_handler`, and `alert_buzz` when it blinks.
+pub fn notify_handlers(icon_id: Icon, duration: Duration, blinks: Option<i32>) -> Vec<Handler> {
+    let mut handlers = vec![notify_handler(icon_id, duration, blinks).into()];
+    if blinks.is_some_and(|blinks| blinks > 0) {
+        handlers.push(alert_buzz().into());
+    }
+    handlers
+}
+
+/// Data that shows `text` with the handler of `notify_handler`.
+pub fn notify_data(text: &str) -> EventData {
+    EventData {
+        value: EventValue::String(text.to_string()),
+        frame: None
+    }
+}
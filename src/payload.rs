@@ -0,0 +1,70 @@
+//! Event data described by a Rust type, so that the handlers of an event can be checked against it.
+//!
+#![cfg_attr(feature = "derive", doc = "```no_run")]
+#![cfg_attr(not(feature = "derive"), doc = "```ignore")]
+//! # use sse_oled_controller::api::SSEngineAPI;
+//! # use sse_oled_controller::api_types::C2SGameEventCreate;
+//! # use sse_oled_controller::types::Handler;
+//! use sse_oled_controller::payload::{self, EventPayload};
+//!
+//! #[derive(EventPayload)]
+//! struct TrackUpdate {
+//!     #[payload(value)]
+//!     progress: i32,
+//!     title: String,
+//!     #[payload(rename = "artist-name")]
+//!     artist: Option<String>
+//! }
+//!
+//! # async fn run(api: SSEngineAPI, event: C2SGameEventCreate, handlers: Vec<Handler>, track: TrackUpdate) -> Result<(), String> {
+//! let binding = payload::bind::<TrackUpdate>(event, handlers)?;
+//! api.bind_event(binding).await.map_err(|e| e.to_string())?;
+//! api.trigger_payload("TRACK_UPDATE".to_string(), &track).await.map_err(|e| e.to_string())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api_types::{C2SGameEventBind, C2SGameEventCreate, EventData};
+use crate::types::Handler;
+
+#[cfg(feature = "derive")]
+pub use sse_oled_controller_derive::EventPayload;
+
+/// A type sent as the data of an event, usually derived with `#[derive(EventPayload)]`.
+pub trait EventPayload {
+    /// Keys of the context frame, that handlers can read with `context-frame-key`.
+    const KEYS: &'static [&'static str];
+
+    fn to_event_data(&self) -> EventData;
+
+    /// Checks that the handlers only read keys of the payload, and reports the ones it doesn't have.
+    /// Keys read by an `arg` are only found when written like `Arg::frame_key` does, see `arg::frame_keys`.
+    fn check_handlers(handlers: &[Handler]) -> Result<(), String> {
+        let mut missing: Vec<&str> = handlers.iter()
+            .flat_map(Handler::context_frame_keys)
+            .filter(|key| !Self::KEYS.contains(key))
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!("{} has no context frame key {}, expected one of {}",
+            std::any::type_name::<Self>(), missing.join(", "), Self::KEYS.join(", ")))
+    }
+}
+
+/// Binds handlers to an event triggered with `P`, fails if a handler reads a key `P` doesn't have.
+pub fn bind<P: EventPayload>(event: C2SGameEventCreate, handlers: Vec<Handler>) -> Result<C2SGameEventBind, String> {
+    P::check_handlers(&handlers)?;
+    Ok(C2SGameEventBind {
+        game: event.game,
+        event: event.event,
+        min_value: event.min_value,
+        max_value: event.max_value,
+        icon_id: event.icon_id,
+        handlers
+    })
+}
@@ -0,0 +1,532 @@
+//! Shares the one screen between providers, see [`Scheduler`].
+//!
+//! Only built with the `controller` feature.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use crate::api::SSEngineAPI;
+use crate::api_types::{C2SGameEventBind, C2SGameEventCreate, EventData};
+use crate::payload::EventPayload;
+use crate::types::Handler;
+
+/// Longest the scheduler sleeps before checking whether another provider wants the screen.
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// What a provider does with the screen on a refresh.
+pub enum Frame {
+    /// Trigger the event with the data
+    Event(String, EventData),
+    /// The screen is up to date, leave it alone
+    Unchanged
+}
+
+/// An event of a provider with the handlers bound to it.
+pub struct EventBinding {
+    pub event: C2SGameEventCreate,
+    pub handlers: Vec<Handler>
+}
+
+impl EventBinding {
+    /// Binding of an event triggered with `P`, fails if a handler reads a key `P` doesn't have.
+    pub fn for_payload<P: EventPayload>(event: C2SGameEventCreate, handlers: Vec<Handler>) -> Result<EventBinding, String> {
+        P::check_handlers(&handlers).map_err(|e| format!("Invalid binding of {}: {}", event.event, e))?;
+
+        Ok(EventBinding {
+            event,
+            handlers
+        })
+    }
+
+    fn into_bind(self) -> C2SGameEventBind {
+        C2SGameEventBind {
+            game: self.event.game,
+            event: self.event.event,
+            min_value: self.event.min_value,
+            max_value: self.event.max_value,
+            icon_id: self.event.icon_id,
+            handlers: self.handlers
+        }
+    }
+}
+
+/// Something that fills the screen while the scheduler lets it.
+///
+/// Providers only deal with their own events and data, the scheduler binds the events,
+/// decides when the provider is on the screen and triggers what it returns.
+/// `frame` is called at the refresh rate of the provider, and right away after `ScreenHandle::refresh`
+/// for providers that learn about changes on their own.
+#[async_trait]
+pub trait ScreenProvider: Send + Sync {
+    /// Events the provider triggers. They are bound at startup,
+    /// and bound again when they changed after the config was reloaded.
+    /// An error, e.g. of `EventBinding::for_payload`, fails `Scheduler::bind`.
+    async fn events(&self) -> Result<Vec<EventBinding>, String> {
+        Ok(vec![])
+    }
+
+    /// Whether the provider has something to show right now.
+    async fn is_ready(&mut self, now: Instant) -> bool;
+
+    /// Called on every refresh while the provider has the screen.
+    /// `fresh` is set when it just got the screen and has to redraw all of it.
+    async fn frame(&mut self, now: Instant, fresh: bool) -> Result<Frame, reqwest::Error>;
+
+    /// Runs a command sent with `screen <provider> ...`, returns the message to print.
+    async fn command(&mut self, _args: Vec<String>) -> Result<String, String> {
+        Err("This provider takes no commands".to_string())
+    }
+}
+
+/// Overrides the scheduling defaults of a provider.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ScreenConfig {
+    pub priority: Option<u8>,
+    /// Seconds the provider keeps the screen before others with the same priority get their turn
+    pub min_dwell_secs: Option<u64>,
+    /// Milliseconds between two refreshes while the provider is on the screen
+    pub refresh_millis: Option<u64>
+}
+
+/// A provider with its scheduling options.
+pub struct Provider {
+    name: &'static str,
+    priority: u8,
+    /// How long the provider keeps the screen before a ready one with the same priority gets its turn
+    min_dwell: Duration,
+    refresh: Duration,
+    source: Box<dyn ScreenProvider>,
+    refreshed: Option<Instant>
+}
+
+impl Provider {
+    pub fn new(name: &'static str, source: impl ScreenProvider + 'static) -> Provider {
+        Provider {
+            name,
+            priority: 0,
+            min_dwell: Duration::ZERO,
+            refresh: POLL_INTERVAL,
+            source: Box::new(source),
+            refreshed: None
+        }
+    }
+
+    /// Ready providers with a higher priority take the screen away from lower ones.
+    pub fn priority(mut self, priority: u8) -> Provider {
+        self.priority = priority;
+        self
+    }
+
+    pub fn min_dwell(mut self, min_dwell: Duration) -> Provider {
+        self.min_dwell = min_dwell;
+        self
+    }
+
+    /// How often `frame` is called while the provider has the screen.
+    pub fn refresh(mut self, refresh: Duration) -> Provider {
+        self.refresh = refresh;
+        self
+    }
+
+    /// Applies the overrides of the config over the defaults of the provider.
+    fn configure(self, config: &ScreenConfig) -> Provider {
+        Provider {
+            priority: config.priority.unwrap_or(self.priority),
+            min_dwell: config.min_dwell_secs.map(Duration::from_secs).unwrap_or(self.min_dwell),
+            refresh: config.refresh_millis.map(Duration::from_millis).unwrap_or(self.refresh),
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ProviderStatus {
+    pub name: String,
+    pub priority: u8,
+    pub min_dwell: Duration,
+    pub refresh: Duration,
+    pub ready: bool
+}
+
+/// What the scheduler is doing.
+#[derive(Clone, Debug, Default)]
+pub struct ScreenStatus {
+    pub active: Option<String>,
+    pub providers: Vec<ProviderStatus>
+}
+
+enum Request {
+    /// Something changed, look at the providers again
+    Wake,
+    /// Refresh the provider now if it's on the screen
+    Refresh(String),
+    /// Bind the events of the providers again if they changed
+    Rebind,
+    Command {
+        provider: String,
+        args: Vec<String>,
+        reply: oneshot::Sender<Result<String, String>>
+    }
+}
+
+/// Talks to the running scheduler, shared with the REPL, the control endpoint and the reloader.
+pub struct ScreenHandle {
+    pub status: Mutex<ScreenStatus>,
+    requests: mpsc::UnboundedSender<Request>
+}
+
+impl ScreenHandle {
+    /// Makes the scheduler check the providers right away, e.g. after a timer was started.
+    pub fn wake(&self) {
+        let _ = self.requests.send(Request::Wake);
+    }
+
+    /// Calls `frame` of the provider right away if it's on the screen, instead of on its next tick.
+    pub fn refresh(&self, provider: &str) {
+        let _ = self.requests.send(Request::Refresh(provider.to_string()));
+    }
+
+    /// Makes the scheduler bind the events of the providers again, after the config changed.
+    pub fn rebind(&self) {
+        let _ = self.requests.send(Request::Rebind);
+    }
+
+    fn send_command(&self, provider: &str, args: Vec<String>) -> Result<oneshot::Receiver<Result<String, String>>, String> {
+        let (reply, response) = oneshot::channel();
+        self.requests.send(Request::Command { provider: provider.to_string(), args, reply })
+            .map_err(|_| "The scheduler is not running".to_string())?;
+        Ok(response)
+    }
+
+    pub async fn command(&self, provider: &str, args: Vec<String>) -> Result<String, String> {
+        self.send_command(provider, args)?.await
+            .map_err(|_| "The scheduler is not running".to_string())?
+    }
+
+    /// Same as `command`, for the REPL thread.
+    pub fn blocking_command(&self, provider: &str, args: Vec<String>) -> Result<String, String> {
+        self.send_command(provider, args)?.blocking_recv()
+            .map_err(|_| "The scheduler is not running".to_string())?
+    }
+}
+
+/// Decides which provider has the screen and triggers its events.
+pub struct Scheduler {
+    api: Arc<SSEngineAPI>,
+    providers: Vec<Provider>,
+    /// Index of the provider on the screen, and since when
+    active: Option<(usize, Instant)>,
+    /// The provider that last had the screen for each priority and since when,
+    /// so that a rotation carries on where it was after a higher priority took the screen
+    turns: HashMap<u8, (usize, Instant)>,
+    handle: Arc<ScreenHandle>,
+    requests: mpsc::UnboundedReceiver<Request>,
+    /// Overrides of the config by provider name
+    screens: HashMap<String, ScreenConfig>,
+    /// Serialized bindings by event name, to only bind again what changed
+    bound: HashMap<String, String>
+}
+
+impl Scheduler {
+    pub fn new(api: Arc<SSEngineAPI>, screens: HashMap<String, ScreenConfig>) -> Scheduler {
+        let (sender, requests) = mpsc::unbounded_channel();
+        Scheduler {
+            api,
+            providers: vec![],
+            active: None,
+            turns: HashMap::new(),
+            handle: Arc::new(ScreenHandle {
+                status: Mutex::new(ScreenStatus::default()),
+                requests: sender
+            }),
+            requests,
+            screens,
+            bound: HashMap::new()
+        }
+    }
+
+    pub fn handle(&self) -> Arc<ScreenHandle> {
+        Arc::clone(&self.handle)
+    }
+
+    /// Adds a provider, with the overrides of the config for its name applied.
+    pub fn provider(mut self, provider: Provider) -> Scheduler {
+        let provider = match self.screens.get(provider.name) {
+            Some(config) => provider.configure(config),
+            None => provider
+        };
+        self.providers.push(provider);
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|provider| provider.name).collect()
+    }
+
+    /// Names of the events of all providers.
+    pub async fn event_names(&self) -> HashSet<String> {
+        let mut names = HashSet::new();
+        for provider in &self.providers {
+            // Invalid bindings are reported by `bind`
+            let events = provider.source.events().await.unwrap_or_default();
+            names.extend(events.into_iter().map(|binding| binding.event.event));
+        }
+
+        names
+    }
+
+    /// Binds the events of all providers that aren't bound yet or changed since.
+    pub async fn bind(&mut self) -> Result<(), String> {
+        for provider in &self.providers {
+            let events = provider.source.events().await.map_err(|e| format!("Provider {}: {}", provider.name, e))?;
+            for binding in events {
+                let create = binding.event.clone();
+                let bind = binding.into_bind();
+                let json = serde_json::to_string(&bind).expect("Bindings are always serializable");
+                match self.bound.get(&create.event) {
+                    Some(bound) if *bound == json => continue,
+                    Some(_) => info!("Rebinding {}", create.event),
+                    None => self.api.new_event(create.clone()).await.map_err(|e| e.to_string())?
+                }
+                self.api.bind_event(bind).await.map_err(|e| e.to_string())?;
+                self.bound.insert(create.event, json);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Picks the provider for the screen among the ready ones.
+    /// Higher priorities win right away, equal ones take turns once the last one dwelled long enough.
+    fn choose(&self, ready: &[bool], now: Instant) -> Option<usize> {
+        let top = self.providers.iter().zip(ready)
+            .filter(|(_, ready)| **ready)
+            .map(|(provider, _)| provider.priority)
+            .max()?;
+
+        let current = match self.turns.get(&top) {
+            Some(&(index, since)) if ready[index] => {
+                if now.duration_since(since) < self.providers[index].min_dwell {
+                    return Some(index);
+                }
+                index
+            },
+            Some(&(index, _)) => index,
+            None => self.providers.len() - 1
+        };
+
+        // The next ready provider with the top priority after the current one, the current one last
+        (1..=self.providers.len())
+            .map(|offset| (current + offset) % self.providers.len())
+            .find(|index| ready[*index] && self.providers[*index].priority == top)
+    }
+
+    /// Puts the chosen provider on the screen, returns whether it just got it.
+    fn advance(&mut self, ready: &[bool], now: Instant) -> bool {
+        let Some(index) = self.choose(ready, now) else {
+            self.active = None;
+            return false;
+        };
+
+        if self.active.is_some_and(|(active, _)| active == index) {
+            return false;
+        }
+
+        info!("Screen switches to {}", self.providers[index].name);
+        // Coming back to an interrupted provider doesn't restart its turn
+        let turn = match self.turns.get(&self.providers[index].priority) {
+            Some(&(last, since)) if last == index => (index, since),
+            _ => (index, now)
+        };
+        self.turns.insert(self.providers[index].priority, turn);
+        self.active = Some(turn);
+        true
+    }
+
+    async fn update_status(&self, ready: &[bool]) {
+        let mut status = self.handle.status.lock().await;
+        status.active = self.active.map(|(index, _)| self.providers[index].name.to_string());
+        status.providers = self.providers.iter().zip(ready).map(|(provider, ready)| ProviderStatus {
+            name: provider.name.to_string(),
+            priority: provider.priority,
+            min_dwell: provider.min_dwell,
+            refresh: provider.refresh,
+            ready: *ready
+        }).collect();
+    }
+
+    async fn handle_request(&mut self, request: Request) {
+        match request {
+            Request::Wake => {},
+            Request::Refresh(name) => {
+                if let Some(provider) = self.providers.iter_mut().find(|provider| provider.name == name) {
+                    provider.refreshed = None;
+                }
+            },
+            Request::Rebind => {
+                if let Err(e) = self.bind().await {
+                    error!("Failed to rebind screen events: {}", e);
+                }
+            },
+            Request::Command { provider, args, reply } => {
+                let result = match self.providers.iter_mut().find(|p| p.name == provider) {
+                    Some(provider) => {
+                        // Whatever the command changed should be on the screen right away
+                        provider.refreshed = None;
+                        provider.source.command(args).await
+                    },
+                    None => Err(format!("Unknown screen provider: {}", provider))
+                };
+                let _ = reply.send(result);
+            }
+        }
+    }
+
+    /// Runs until the task is aborted.
+    pub async fn run(mut self) {
+        loop {
+            let now = Instant::now();
+            let mut ready = vec![];
+            for provider in &mut self.providers {
+                ready.push(provider.source.is_ready(now).await);
+            }
+
+            let fresh = self.advance(&ready, now);
+
+            let mut wait = POLL_INTERVAL;
+            if let Some((index, _)) = self.active {
+                let provider = &mut self.providers[index];
+                let due = provider.refreshed.map(|at| now.duration_since(at) >= provider.refresh).unwrap_or(true);
+                if fresh || due {
+                    provider.refreshed = Some(now);
+                    match provider.source.frame(now, fresh).await {
+                        Ok(Frame::Event(event, data)) => {
+                            if let Err(e) = self.api.trigger_event(event.clone(), Some(data)).await {
+                                error!("Failed to trigger {} for {}: {}", event, provider.name, e);
+                            }
+                        },
+                        Ok(Frame::Unchanged) => {},
+                        Err(e) => error!("Provider {} failed: {}", provider.name, e)
+                    }
+                }
+                wait = wait.min(provider.refresh.saturating_sub(now.duration_since(provider.refreshed.unwrap_or(now))));
+            }
+
+            self.update_status(&ready).await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {},
+                request = self.requests.recv() => {
+                    if let Some(request) = request {
+                        self.handle_request(request).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Idle;
+
+    #[async_trait]
+    impl ScreenProvider for Idle {
+        async fn is_ready(&mut self, _now: Instant) -> bool {
+            false
+        }
+
+        async fn frame(&mut self, _now: Instant, _fresh: bool) -> Result<Frame, reqwest::Error> {
+            Ok(Frame::Unchanged)
+        }
+    }
+
+    /// Providers named by their index, with their priority and min dwell in seconds.
+    fn scheduler(providers: &[(u8, u64)]) -> Scheduler {
+        const NAMES: [&str; 4] = ["0", "1", "2", "3"];
+        let api = Arc::new(SSEngineAPI::new(Some("127.0.0.1:1")));
+        providers.iter().zip(NAMES).fold(Scheduler::new(api, HashMap::new()), |scheduler, (&(priority, dwell), name)| {
+            scheduler.provider(Provider::new(name, Idle).priority(priority).min_dwell(Duration::from_secs(dwell)))
+        })
+    }
+
+    fn active(scheduler: &Scheduler) -> Option<usize> {
+        scheduler.active.map(|(index, _)| index)
+    }
+
+    #[test]
+    fn higher_priority_preempts() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(&[(0, 60), (100, 0)]);
+
+        assert!(scheduler.advance(&[true, false], start));
+        assert_eq!(active(&scheduler), Some(0));
+
+        // Right away, however long the min dwell of the active one
+        assert!(scheduler.advance(&[true, true], start + Duration::from_secs(1)));
+        assert_eq!(active(&scheduler), Some(1));
+
+        assert!(!scheduler.advance(&[true, true], start + Duration::from_secs(2)));
+        assert!(scheduler.advance(&[true, false], start + Duration::from_secs(3)));
+        assert_eq!(active(&scheduler), Some(0));
+    }
+
+    #[test]
+    fn equal_priorities_take_turns_after_min_dwell() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(&[(0, 10), (0, 5), (0, 0)]);
+        let ready = [true, true, false];
+
+        scheduler.advance(&ready, start);
+        assert_eq!(active(&scheduler), Some(0));
+        assert!(!scheduler.advance(&ready, start + Duration::from_secs(9)));
+        assert_eq!(active(&scheduler), Some(0));
+
+        // The one that isn't ready is skipped
+        assert!(scheduler.advance(&ready, start + Duration::from_secs(10)));
+        assert_eq!(active(&scheduler), Some(1));
+        assert!(scheduler.advance(&ready, start + Duration::from_secs(15)));
+        assert_eq!(active(&scheduler), Some(0));
+
+        // Alone, it keeps the screen
+        assert!(!scheduler.advance(&[true, false, false], start + Duration::from_secs(60)));
+        assert_eq!(active(&scheduler), Some(0));
+    }
+
+    #[test]
+    fn rotation_resumes_after_preemption() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(&[(0, 10), (0, 10), (100, 0)]);
+
+        scheduler.advance(&[true, true, false], start);
+        assert_eq!(active(&scheduler), Some(0));
+
+        scheduler.advance(&[true, true, true], start + Duration::from_secs(4));
+        assert_eq!(active(&scheduler), Some(2));
+
+        // Back to the interrupted one, its turn counts from when it started
+        assert!(scheduler.advance(&[true, true, false], start + Duration::from_secs(6)));
+        assert_eq!(active(&scheduler), Some(0));
+        assert_eq!(scheduler.active, Some((0, start)));
+        assert!(scheduler.advance(&[true, true, false], start + Duration::from_secs(10)));
+        assert_eq!(active(&scheduler), Some(1));
+    }
+
+    #[test]
+    fn nothing_ready_leaves_the_screen() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(&[(0, 0)]);
+
+        scheduler.advance(&[true], start);
+        assert!(!scheduler.advance(&[false], start));
+        assert_eq!(active(&scheduler), None);
+        assert_eq!(scheduler.choose(&[false], start), None);
+    }
+}
@@ -0,0 +1,463 @@
+//! Builders for screen handlers, instead of nesting the structs of [`crate::types`].
+//!
+//! ```
+//! use sse_oled_controller::screen::{progress_bar, text, Screen};
+//! use sse_oled_controller::types::{Icon, ScreenHandler};
+//!
+//! let handler: ScreenHandler = Screen::new()
+//!     .frame(Screen::multi_line()
+//!         .line(text().prefix("Now ").frame_key("curr_game"))
+//!         .line(text().bold())
+//!         .line(progress_bar())
+//!         .icon(Icon::Timer))
+//!     .build();
+//! ```
+//!
+//! Anything that isn't set keeps the default of the engine: frames without an icon that stay
+//! until the next event, text that doesn't wrap, drawn on every screen.
+
+use crate::api_types::C2SGameEventCreate;
+use crate::types::{
+    DataAccessorData, FrameModifiersData, Icon, ImageFrameData, LineContent, LineData, MultiLineFrameData,
+    OLEDDeviceType, ProgressBarModifierData, RangeScreenData, Repeat, ScreenData, ScreenFrameData, ScreenHandler,
+    SingleLineFrameData, TextModifierData
+};
+
+/// Builds a `ScreenHandler` from its frames, shown one after another.
+#[derive(Default)]
+pub struct Screen {
+    handler: ScreenHandler
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen::default()
+    }
+
+    /// Only draw on screens of this size, all screens by default.
+    pub fn device(mut self, device: OLEDDeviceType) -> Screen {
+        self.handler.device_type = device.into();
+        self
+    }
+
+    pub fn frame(mut self, frame: impl Into<ScreenFrameData>) -> Screen {
+        self.handler.datas.push(ScreenData::FrameData(frame.into()));
+        self
+    }
+
+    /// Frames shown while the value is in a range, instead of the other frames.
+    pub fn range(mut self, range: Range) -> Screen {
+        self.handler.datas.push(ScreenData::RangeData(range.data));
+        self
+    }
+
+    pub fn build(self) -> ScreenHandler {
+        self.handler
+    }
+
+    /// A frame of one line, with the icon on its left.
+    pub fn single_line(line: impl Into<LineData>) -> SingleLine {
+        let line = line.into();
+        SingleLine {
+            data: SingleLineFrameData {
+                content: line.content,
+                frame_modifiers_data: None,
+                data_accessor_data: line.data_accessor_data
+            }
+        }
+    }
+
+    /// A frame of up to three lines, depending on the screen.
+    pub fn multi_line() -> MultiLine {
+        MultiLine {
+            data: MultiLineFrameData {
+                frame_modifiers_data: None,
+                lines: vec![]
+            }
+        }
+    }
+
+    /// A frame of one bit per pixel, row by row, e.g. 640 bytes for the 128x40 screens.
+    pub fn image(image_data: Vec<u8>) -> Image {
+        Image {
+            data: ImageFrameData {
+                frame_modifiers_data: None,
+                image_data
+            }
+        }
+    }
+}
+
+impl From<Screen> for ScreenHandler {
+    fn from(screen: Screen) -> Self {
+        screen.build()
+    }
+}
+
+impl From<Screen> for crate::types::Handler {
+    fn from(screen: Screen) -> Self {
+        screen.build().into()
+    }
+}
+
+/// A line of text, shows the event value unless it reads `frame_key` or `arg`.
+pub fn text() -> Text {
+    Text {
+        modifiers: TextModifierData::default(),
+        accessor: None
+    }
+}
+
+/// A bar filled to the value of the event, between its `min_value` and `max_value`.
+pub fn progress_bar() -> ProgressBar {
+    ProgressBar {
+        accessor: None
+    }
+}
+
+pub struct Text {
+    modifiers: TextModifierData,
+    accessor: Option<DataAccessorData>
+}
+
+impl Text {
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Text {
+        self.modifiers.prefix = prefix.into();
+        self
+    }
+
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Text {
+        self.modifiers.suffix = suffix.into();
+        self
+    }
+
+    pub fn bold(mut self) -> Text {
+        self.modifiers.bold = true;
+        self
+    }
+
+    /// Wraps the text over this many lines instead of scrolling it, 0 doesn't wrap.
+    pub fn wrap(mut self, lines: i32) -> Text {
+        self.modifiers.wrap = lines;
+        self
+    }
+
+    /// A line without text, e.g. to leave only the prefix.
+    pub fn no_text(mut self) -> Text {
+        self.modifiers.has_text = false;
+        self
+    }
+
+    /// Shows this key of the context frame instead of the value.
+    pub fn frame_key(mut self, key: impl Into<String>) -> Text {
+        self.accessor.get_or_insert_with(DataAccessorData::default).context_frame_key = Some(key.into());
+        self
+    }
+
+    /// Shows the result of an argument expression of the engine instead of the value.
+    pub fn arg(mut self, arg: impl Into<String>) -> Text {
+        self.accessor.get_or_insert_with(DataAccessorData::default).arg = Some(arg.into());
+        self
+    }
+}
+
+impl From<Text> for LineData {
+    fn from(text: Text) -> Self {
+        LineData {
+            content: LineContent::Text(text.modifiers),
+            data_accessor_data: text.accessor
+        }
+    }
+}
+
+pub struct ProgressBar {
+    accessor: Option<DataAccessorData>
+}
+
+impl ProgressBar {
+    /// Fills the bar from this key of the context frame instead of the value.
+    pub fn frame_key(mut self, key: impl Into<String>) -> ProgressBar {
+        self.accessor.get_or_insert_with(DataAccessorData::default).context_frame_key = Some(key.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> ProgressBar {
+        self.accessor.get_or_insert_with(DataAccessorData::default).arg = Some(arg.into());
+        self
+    }
+}
+
+impl From<ProgressBar> for LineData {
+    fn from(bar: ProgressBar) -> Self {
+        LineData {
+            content: LineContent::ProgressBar(ProgressBarModifierData::default()),
+            data_accessor_data: bar.accessor
+        }
+    }
+}
+
+/// Setters of the icon, length and repeats of a frame.
+macro_rules! frame_modifiers {
+    ($builder:ident) => {
+        impl $builder {
+            fn modifiers(&mut self) -> &mut FrameModifiersData {
+                self.data.frame_modifiers_data.get_or_insert_with(FrameModifiersData::default)
+            }
+
+            pub fn icon(mut self, icon: Icon) -> $builder {
+                self.modifiers().icon_id = icon;
+                self
+            }
+
+            /// How long the frame is shown before the next one, 0 keeps it until the next event.
+            pub fn length_millis(mut self, length_millis: i32) -> $builder {
+                self.modifiers().length_millis = length_millis;
+                self
+            }
+
+            /// Shows the frames of the handler this many times.
+            pub fn repeat(mut self, count: i32) -> $builder {
+                self.modifiers().repeats = Repeat::Counts(count);
+                self
+            }
+
+            /// Shows the frames of the handler until the next event.
+            pub fn repeat_forever(mut self) -> $builder {
+                self.modifiers().repeats = Repeat::Infinite(true);
+                self
+            }
+        }
+    };
+}
+
+pub struct SingleLine {
+    data: SingleLineFrameData
+}
+
+frame_modifiers!(SingleLine);
+
+impl From<SingleLine> for ScreenFrameData {
+    fn from(frame: SingleLine) -> Self {
+        ScreenFrameData::SingleLine(frame.data)
+    }
+}
+
+pub struct MultiLine {
+    data: MultiLineFrameData
+}
+
+frame_modifiers!(MultiLine);
+
+impl MultiLine {
+    pub fn line(mut self, line: impl Into<LineData>) -> MultiLine {
+        self.data.lines.push(line.into());
+        self
+    }
+}
+
+impl From<MultiLine> for ScreenFrameData {
+    fn from(frame: MultiLine) -> Self {
+        ScreenFrameData::MultiLine(frame.data)
+    }
+}
+
+pub struct Image {
+    data: ImageFrameData
+}
+
+frame_modifiers!(Image);
+
+impl From<Image> for ScreenFrameData {
+    fn from(frame: Image) -> Self {
+        ScreenFrameData::Image(frame.data)
+    }
+}
+
+pub struct Range {
+    data: RangeScreenData
+}
+
+impl Range {
+    /// Frames shown while the value is between `low` and `high`, both included.
+    pub fn new(low: i32, high: i32) -> Range {
+        Range {
+            data: RangeScreenData {
+                low,
+                high,
+                datas: vec![]
+            }
+        }
+    }
+
+    pub fn frame(mut self, frame: impl Into<ScreenFrameData>) -> Range {
+        self.data.datas.push(frame.into());
+        self
+    }
+}
+
+/// Splits the values of an event into bands that follow each other, each with its own frames,
+/// e.g. a battery that is low, medium or full:
+///
+/// ```
+/// # use sse_oled_controller::api_types::C2SGameEventCreate;
+/// # use sse_oled_controller::screen::{text, Bands, Screen};
+/// # use sse_oled_controller::types::Icon;
+/// # let event = C2SGameEventCreate { game: "G".to_string(), event: "BATTERY".to_string(), min_value: 0,
+/// #     max_value: 100, icon_id: Icon::Lightning, value_optional: false };
+/// let handler = Bands::new(&event)
+///     .band(15, Screen::single_line(text().prefix("Low ").bold()).icon(Icon::Lightning))
+///     .band(60, Screen::single_line(text().prefix("Battery ")))
+///     .rest(Screen::single_line(text().prefix("Full ")))
+///     .build()
+///     .unwrap();
+/// ```
+pub struct Bands {
+    min_value: i32,
+    max_value: i32,
+    screen: Screen,
+    ranges: Vec<Range>,
+    problems: Vec<String>
+}
+
+impl Bands {
+    pub fn new(event: &C2SGameEventCreate) -> Bands {
+        Bands {
+            min_value: event.min_value,
+            max_value: event.max_value,
+            screen: Screen::new(),
+            ranges: vec![],
+            problems: vec![]
+        }
+    }
+
+    /// Splits the values into `count` bands of about the same size, `frame` builds the frame of each one from its index.
+    pub fn even<F: Into<ScreenFrameData>>(event: &C2SGameEventCreate, count: usize, frame: impl Fn(usize) -> F) -> Bands {
+        let mut bands = Bands::new(event);
+        let span = (event.max_value as i64 - event.min_value as i64 + 1).max(0);
+        for i in 0..count {
+            let high = event.min_value as i64 + span * (i as i64 + 1) / count as i64 - 1;
+            bands = bands.band(high as i32, frame(i));
+        }
+        bands
+    }
+
+    pub fn device(mut self, device: OLEDDeviceType) -> Bands {
+        self.screen = self.screen.device(device);
+        self
+    }
+
+    /// A band from the value after the previous band, or `min_value`, up to `high`.
+    /// Fails on `build` when the previous band already reached `max_value`.
+    pub fn band(mut self, high: i32, frame: impl Into<ScreenFrameData>) -> Bands {
+        let low = match self.ranges.last() {
+            Some(range) if range.data.high >= self.max_value => {
+                self.problems.push(format!("A band up to {} follows one that already reached max_value {}", high, self.max_value));
+                return self;
+            },
+            Some(range) => range.data.high + 1,
+            None => self.min_value
+        };
+        self.ranges.push(Range::new(low, high).frame(frame));
+        self
+    }
+
+    /// Another frame of the last band, shown after the ones before.
+    pub fn then(mut self, frame: impl Into<ScreenFrameData>) -> Bands {
+        match self.ranges.pop() {
+            Some(range) => self.ranges.push(range.frame(frame)),
+            None => self.problems.push("A frame was added before the first band".to_string())
+        }
+        self
+    }
+
+    /// The band of the values left, up to `max_value`.
+    pub fn rest(self, frame: impl Into<ScreenFrameData>) -> Bands {
+        let max_value = self.max_value;
+        self.band(max_value, frame)
+    }
+
+    /// Fails with the problems of the bands, e.g. when they don't reach `max_value` or `then` came before any band.
+    pub fn build(self) -> Result<ScreenHandler, Vec<String>> {
+        let mut problems = self.problems;
+        if self.ranges.is_empty() {
+            problems.push("No bands".to_string());
+        }
+
+        let handler = self.ranges.into_iter().fold(self.screen, Screen::range).build();
+        problems.extend(handler.problems(self.min_value, self.max_value));
+        if problems.is_empty() { Ok(handler) } else { Err(problems) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{Icon, ScreenData};
+    use super::*;
+
+    fn event(min_value: i32, max_value: i32) -> C2SGameEventCreate {
+        C2SGameEventCreate {
+            game: "TEST".to_string(),
+            event: "BANDS".to_string(),
+            min_value,
+            max_value,
+            icon_id: Icon::NoIcon,
+            value_optional: false
+        }
+    }
+
+    fn bounds(handler: &ScreenHandler) -> Vec<(i32, i32)> {
+        handler.datas.iter().filter_map(|data| match data {
+            ScreenData::RangeData(range) => Some((range.low, range.high)),
+            ScreenData::FrameData(_) => None
+        }).collect()
+    }
+
+    #[test]
+    fn even_split() {
+        let handler = Bands::even(&event(0, 99), 4, |_| Screen::single_line(text())).build().unwrap();
+        assert_eq!(bounds(&handler), [(0, 24), (25, 49), (50, 74), (75, 99)]);
+
+        let handler = Bands::even(&event(1, 10), 3, |_| Screen::single_line(text())).build().unwrap();
+        assert_eq!(bounds(&handler), [(1, 3), (4, 6), (7, 10)]);
+    }
+
+    #[test]
+    fn rest_reaches_max_value() {
+        let handler = Bands::new(&event(0, 100))
+            .band(9, Screen::single_line(text()))
+            .rest(Screen::single_line(text()))
+            .build().unwrap();
+        assert_eq!(bounds(&handler), [(0, 9), (10, 100)]);
+    }
+
+    #[test]
+    fn gap_up_to_max_value() {
+        let problems = Bands::new(&event(0, 100)).band(49, Screen::single_line(text())).build().unwrap_err();
+        assert_eq!(problems, ["datas: no range covers 50 to 100"]);
+    }
+
+    #[test]
+    fn overlapping_band() {
+        let problems = Bands::new(&event(0, 100))
+            .band(49, Screen::single_line(text()))
+            .band(20, Screen::single_line(text()))
+            .rest(Screen::single_line(text()))
+            .build().unwrap_err();
+        assert_eq!(problems, ["datas[1]: low 50 is greater than high 20", "datas[2]: 21 to 100 overlaps another range"]);
+    }
+
+    #[test]
+    fn band_after_max_value() {
+        let problems = Bands::new(&event(0, 100))
+            .band(100, Screen::single_line(text()))
+            .rest(Screen::single_line(text()))
+            .build().unwrap_err();
+        assert_eq!(problems, ["A band up to 100 follows one that already reached max_value 100"]);
+
+        let problems = Bands::new(&event(0, i32::MAX))
+            .rest(Screen::single_line(text()))
+            .band(i32::MAX, Screen::single_line(text()))
+            .build().unwrap_err();
+        assert_eq!(problems, [format!("A band up to {} follows one that already reached max_value {}", i32::MAX, i32::MAX)]);
+    }
+}
@@ -1,18 +1,285 @@
+//! Handlers bound to events: screens with their frames, tactile feedback and RGB lighting.
+
+use std::str::FromStr;
 use std::string::ToString;
 use derivative::Derivative;
-use serde::{Serialize, Deserialize};
+use serde::{Deserializer, Serialize, Deserialize};
+use serde::de::{Error as _, IgnoredAny};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 use serde_with::{serde_as, Bytes};
 
+/// Any handler that can be bound to an event.
+/// The variant is picked from `device-type` and `mode`, so that errors come from the type of the handler.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+pub enum Handler {
+    Screen(ScreenHandler),
+    Tactile(TactileHandler),
+    Color(ColorHandler)
+}
+
+/// The fields that tell the types of handlers apart.
+#[derive(Deserialize)]
+struct HandlerKind<'a> {
+    #[serde(rename = "device-type", borrow, default)]
+    device_type: Option<&'a str>,
+    #[serde(borrow, default)]
+    mode: Option<&'a str>,
+    #[serde(default)]
+    pattern: Option<IgnoredAny>
+}
+
+impl HandlerKind<'_> {
+    /// `screen`, `tactile` or `color`. Both screen and tactile handlers may leave out `device-type` and `mode`.
+    fn name(&self) -> &'static str {
+        match (self.device_type, self.mode) {
+            (Some("tactile"), _) | (_, Some("vibrate")) => "tactile",
+            (None, None) if self.pattern.is_some() => "tactile",
+            (None, None) | (_, Some("screen")) => "screen",
+            (Some(device_type), None) if device_type.starts_with("screened") => "screen",
+            _ => "color"
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Handler {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let kind = HandlerKind::deserialize(&value).map_err(D::Error::custom)?.name();
+        let handler = match kind {
+            "screen" => ScreenHandler::deserialize(value).map(Handler::Screen),
+            "tactile" => TactileHandler::deserialize(value).map(Handler::Tactile),
+            _ => ColorHandler::deserialize(value).map(Handler::Color)
+        };
+
+        handler.map_err(|e| D::Error::custom(format!("{} handler: {}", kind, e)))
+    }
+}
+
+impl Handler {
+    /// Parses a handler from JSON text. Unlike `Deserialize`, the errors keep the line and column
+    /// inside the handler where the problem was found.
+    pub fn from_json(json: &str) -> Result<Handler, serde_json::Error> {
+        match serde_json::from_str::<HandlerKind>(json)?.name() {
+            "screen" => serde_json::from_str(json).map(Handler::Screen),
+            "tactile" => serde_json::from_str(json).map(Handler::Tactile),
+            _ => serde_json::from_str(json).map(Handler::Color)
+        }
+    }
+
+    /// Problems of the handler, see `ScreenHandler::problems`, `TactileHandler::problems` and `ColorHandler::problems`.
+    pub fn problems(&self, min_value: i32, max_value: i32) -> Vec<String> {
+        match self {
+            Handler::Screen(handler) => handler.problems(min_value, max_value),
+            Handler::Tactile(handler) => handler.problems(min_value, max_value),
+            Handler::Color(handler) => handler.problems(min_value, max_value)
+        }
+    }
+
+    /// Keys of the context frame the handler reads, see `DataAccessorData::context_frame_keys` for `arg`s.
+    pub fn context_frame_keys(&self) -> Vec<&str> {
+        match self {
+            Handler::Screen(handler) => handler.datas.iter()
+                .flat_map(|data| match data {
+                    ScreenData::FrameData(frame) => vec![frame],
+                    ScreenData::RangeData(range) => range.datas.iter().collect()
+                })
+                .flat_map(ScreenFrameData::context_frame_keys)
+                .collect(),
+            Handler::Tactile(_) => vec![],
+            Handler::Color(handler) => match handler.mode {
+                ColorMode::Bitmap => vec![crate::bitmap::FRAME_KEY],
+                _ => handler.context_frame_key.as_deref().into_iter().collect()
+            }
+        }
+    }
+}
+
+impl From<ScreenHandler> for Handler {
+    fn from(handler: ScreenHandler) -> Self {
+        Handler::Screen(handler)
+    }
+}
+
+impl From<TactileHandler> for Handler {
+    fn from(handler: TactileHandler) -> Self {
+        Handler::Tactile(handler)
+    }
+}
+
+impl From<ColorHandler> for Handler {
+    fn from(handler: ColorHandler) -> Self {
+        Handler::Color(handler)
+    }
+}
+
+/// Draws the data of an event on the OLED screen, one frame per entry of `datas`.
 #[derive(Serialize, Deserialize, Debug, Derivative)]
+#[derivative(Default)]
 pub struct ScreenHandler {
-    #[serde(rename = "device-type")]
+    #[serde(rename = "device-type", default = "ScreenHandler::default_device_type")]
+    #[derivative(Default(value = "ScreenHandler::default_device_type()"))]
     pub device_type: String,
+    #[serde(default = "ScreenHandler::default_zone")]
+    #[derivative(Default(value = "ScreenHandler::default_zone()"))]
     pub zone: String,
-    #[derivative(Default(value = "screen"))]
+    #[derivative(Default(value = "ScreenHandler::default_mode()"))]
+    #[serde(default = "ScreenHandler::default_mode")]
     pub mode: String,
     pub datas: Vec<ScreenData>
 }
 
+impl ScreenHandler {
+    fn default_device_type() -> String {
+        "screened".to_string()
+    }
+
+    fn default_zone() -> String {
+        "one".to_string()
+    }
+
+    fn default_mode() -> String {
+        "screen".to_string()
+    }
+
+    /// Problems that make the engine reject the handler or ignore a part of it, for an event from
+    /// `min_value` to `max_value`. Each one starts with the path of the data, e.g. `datas[0].lines[1].wrap`.
+    pub fn problems(&self, min_value: i32, max_value: i32) -> Vec<String> {
+        let mut problems = vec![];
+        let mut frames = vec![];
+        let mut ranges = vec![];
+        for (i, data) in self.datas.iter().enumerate() {
+            match data {
+                ScreenData::FrameData(frame) => frames.push((format!("datas[{}]", i), frame)),
+                ScreenData::RangeData(range) => ranges.push((i, range))
+            }
+        }
+
+        if self.datas.is_empty() {
+            problems.push("datas: no frames".to_string());
+        }
+        if !frames.is_empty() && !ranges.is_empty() {
+            problems.push("datas: mixes frames and ranges, the engine expects one kind".to_string());
+        }
+        frame_problems(&frames, &self.device_type, &mut problems);
+
+        for (i, range) in &ranges {
+            if range.datas.is_empty() {
+                problems.push(format!("datas[{}].datas: no frames", i));
+            }
+            let frames: Vec<_> = range.datas.iter().enumerate()
+                .map(|(j, frame)| (format!("datas[{}].datas[{}]", i, j), frame))
+                .collect();
+            frame_problems(&frames, &self.device_type, &mut problems);
+        }
+
+        if !ranges.is_empty() {
+            let bounds = ranges.iter().map(|(i, range)| (*i, range.low, range.high)).collect();
+            range_problems("datas", bounds, min_value, max_value, &mut problems);
+        }
+
+        problems
+    }
+}
+
+/// Checks frames shown one after another, `frames` has the path of each frame.
+fn frame_problems(frames: &[(String, &ScreenFrameData)], device_type: &str, problems: &mut Vec<String>) {
+    for (i, (path, frame)) in frames.iter().enumerate() {
+        match frame {
+            ScreenFrameData::MultiLine(frame) => {
+                if frame.lines.is_empty() {
+                    problems.push(format!("{}.lines: no lines", path));
+                }
+                for (j, line) in frame.lines.iter().enumerate() {
+                    if matches!(&line.content, LineContent::Text(text) if text.wrap != 0) {
+                        problems.push(format!("{}.lines[{}].wrap: only single line frames wrap, use 0", path, j));
+                    }
+                }
+            },
+            ScreenFrameData::Image(frame) => {
+                let length = frame.image_data.len();
+                match image_length(device_type) {
+                    Some(expected) if length != expected => problems.push(format!(
+                        "{}.image-data: {} bytes, {} screens take {}", path, length, device_type, expected)),
+                    None if !IMAGE_LENGTHS.contains(&length) => problems.push(format!(
+                        "{}.image-data: {} bytes, expected 576, 640, 768 or 832 for one of the screen sizes", path, length)),
+                    _ => {}
+                }
+            },
+            ScreenFrameData::SingleLine(_) | ScreenFrameData::DynamicImage(_) => {}
+        }
+
+        let Some(modifiers) = frame.frame_modifiers_data() else { continue };
+        if modifiers.length_millis < 0 {
+            problems.push(format!("{}.length-millis: {} is negative", path, modifiers.length_millis));
+        }
+        match modifiers.repeats {
+            Repeat::Infinite(false) => {},
+            _ if i + 1 < frames.len() => problems.push(format!("{}.repeats: only the last frame repeats", path)),
+            Repeat::Counts(count) if count < 0 => problems.push(format!("{}.repeats: {} is negative", path, count)),
+            _ => {}
+        }
+    }
+
+    // The repeats of the last frame show all the frames again, a frame of length 0 would stay until the next event instead
+    let Some(repeats) = frames.last().and_then(|(_, frame)| frame.frame_modifiers_data()).map(|modifiers| &modifiers.repeats) else { return };
+    let rule = match repeats {
+        Repeat::Infinite(true) => "the last frame repeats forever",
+        Repeat::Counts(count) if *count > 0 => "the last frame repeats",
+        _ => return
+    };
+    for (path, frame) in frames {
+        if frame.frame_modifiers_data().map_or(0, |modifiers| modifiers.length_millis) == 0 {
+            problems.push(format!("{}.length-millis: {}, every frame needs a length above 0", path, rule));
+        }
+    }
+}
+
+/// Checks that the ranges, by index with their low and high, cover every value of the event once.
+/// `path` is the list of the ranges, e.g. `datas`.
+fn range_problems(path: &str, mut ranges: Vec<(usize, i32, i32)>, min_value: i32, max_value: i32, problems: &mut Vec<String>) {
+    for &(i, low, high) in &ranges {
+        if low > high {
+            problems.push(format!("{}[{}]: low {} is greater than high {}", path, i, low, high));
+        } else if high < min_value || low > max_value {
+            problems.push(format!("{}[{}]: {} to {} is outside of the values {} to {}", path, i, low, high, min_value, max_value));
+        }
+    }
+    ranges.retain(|(_, low, high)| low <= high);
+    ranges.sort_by_key(|(_, low, _)| *low);
+
+    // First value that no range covered yet, in an i64 since it goes past `i32::MAX` once a range ends there
+    let mut next = min_value as i64;
+    let mut previous_high = None;
+    for (i, low, high) in ranges {
+        if previous_high.is_some_and(|previous| low <= previous) {
+            problems.push(format!("{}[{}]: {} to {} overlaps another range", path, i, low, high));
+        } else if low as i64 > next && next <= max_value as i64 {
+            problems.push(format!("{}: no range covers {} to {}", path, next, (low - 1).min(max_value)));
+        }
+        next = next.max(high as i64 + 1);
+        previous_high = Some(previous_high.map_or(high, |previous: i32| previous.max(high)));
+    }
+
+    if next <= max_value as i64 {
+        problems.push(format!("{}: no range covers {} to {}", path, next, max_value));
+    }
+}
+
+/// Length of the images of the screens of `screened-<width>x<height>` devices, one bit per pixel.
+fn image_length(device_type: &str) -> Option<usize> {
+    let (width, height) = device_type.strip_prefix("screened-")?.split_once('x')?;
+    Some(width.parse::<usize>().ok()? * height.parse::<usize>().ok()? / 8)
+}
+
+const IMAGE_LENGTHS: [usize; 4] = [
+    std::mem::size_of::<RawImageRival>(),
+    std::mem::size_of::<RawImageApex>(),
+    std::mem::size_of::<RawImageArctisProWireless>(),
+    std::mem::size_of::<RawImageGameDAC>()
+];
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum OLEDDeviceType {
@@ -22,9 +289,9 @@ pub enum OLEDDeviceType {
     GameDAC
 }
 
-impl Into<String> for OLEDDeviceType {
-    fn into(self) -> String {
-        match self {
+impl From<OLEDDeviceType> for String {
+    fn from(device: OLEDDeviceType) -> String {
+        match device {
             OLEDDeviceType::ApexSeries => "screened-128x40".to_string(),
             OLEDDeviceType::RivalSeries => "screened-128x36".to_string(),
             OLEDDeviceType::ArctisProWireless => "screened-128x48".to_string(),
@@ -35,6 +302,7 @@ impl Into<String> for OLEDDeviceType {
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum ScreenData {
     FrameData(ScreenFrameData),
     RangeData(RangeScreenData)
@@ -42,6 +310,7 @@ pub enum ScreenData {
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum ScreenFrameData {
     SingleLine(SingleLineFrameData),
     MultiLine(MultiLineFrameData),
@@ -49,6 +318,30 @@ pub enum ScreenFrameData {
     DynamicImage(DynamicImageFrameData)
 }
 
+impl ScreenFrameData {
+    fn frame_modifiers_data(&self) -> Option<&FrameModifiersData> {
+        match self {
+            ScreenFrameData::SingleLine(frame) => frame.frame_modifiers_data.as_ref(),
+            ScreenFrameData::MultiLine(frame) => frame.frame_modifiers_data.as_ref(),
+            ScreenFrameData::Image(frame) => frame.frame_modifiers_data.as_ref(),
+            ScreenFrameData::DynamicImage(_) => None
+        }
+    }
+
+    fn context_frame_keys(&self) -> Vec<&str> {
+        let accessors = match self {
+            ScreenFrameData::SingleLine(frame) => vec![&frame.data_accessor_data],
+            ScreenFrameData::MultiLine(frame) => frame.lines.iter().map(|line| &line.data_accessor_data).collect(),
+            ScreenFrameData::Image(_) | ScreenFrameData::DynamicImage(_) => vec![]
+        };
+
+        accessors.into_iter()
+            .flatten()
+            .flat_map(DataAccessorData::context_frame_keys)
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SingleLineFrameData {
     #[serde(flatten)]
@@ -119,6 +412,7 @@ pub struct LineData {
 }
 
 #[derive(Serialize, Deserialize, Debug, Derivative)]
+#[derivative(Default)]
 pub struct FrameModifiersData {
     #[serde(rename = "length-millis")]
     #[derivative(Default(value = "0"))]
@@ -126,13 +420,14 @@ pub struct FrameModifiersData {
     #[serde(rename = "icon-id")]
     #[derivative(Default(value = "Icon::NoIcon"))]
     pub icon_id: Icon,
-    #[derivative(Default(value = "Infinite(false)"))]
+    #[derivative(Default(value = "Repeat::Infinite(false)"))]
     pub repeats: Repeat
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(untagged)]
+/// Icons of the engine, shown left of the text.
+#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
+#[allow(clippy::upper_case_acronyms, clippy::enum_variant_names)]
 pub enum Icon {
     NoIcon = 0,
     HealthA = 1,
@@ -177,6 +472,64 @@ pub enum Icon {
     Temperature = 43,
 }
 
+impl Icon {
+    pub const ALL: [(&'static str, Icon); 41] = [
+        ("NoIcon", Icon::NoIcon),
+        ("HealthA", Icon::HealthA),
+        ("Armor", Icon::Armor),
+        ("Ammo", Icon::Ammo),
+        ("Money", Icon::Money),
+        ("Flashbang", Icon::Flashbang),
+        ("KillsA", Icon::KillsA),
+        ("Headshot", Icon::Headshot),
+        ("Helmet", Icon::Helmet),
+        ("Hunger", Icon::Hunger),
+        ("Air", Icon::Air),
+        ("Compass", Icon::Compass),
+        ("Tool", Icon::Tool),
+        ("ManaA", Icon::ManaA),
+        ("Clock", Icon::Clock),
+        ("Lightning", Icon::Lightning),
+        ("Backpack", Icon::Backpack),
+        ("AtSymbol", Icon::AtSymbol),
+        ("Muted", Icon::Muted),
+        ("Talking", Icon::Talking),
+        ("Connect", Icon::Connect),
+        ("Disconnect", Icon::Disconnect),
+        ("Music", Icon::Music),
+        ("Play", Icon::Play),
+        ("Pause", Icon::Pause),
+        ("CPU", Icon::CPU),
+        ("GPU", Icon::GPU),
+        ("RAM", Icon::RAM),
+        ("Assists", Icon::Assists),
+        ("CreepScore", Icon::CreepScore),
+        ("Dead", Icon::Dead),
+        ("Dragon", Icon::Dragon),
+        ("Enemies", Icon::Enemies),
+        ("GameStart", Icon::GameStart),
+        ("Gold", Icon::Gold),
+        ("HealthB", Icon::HealthB),
+        ("KillsB", Icon::KillsB),
+        ("ManaB", Icon::ManaB),
+        ("Teammates", Icon::Teammates),
+        ("Timer", Icon::Timer),
+        ("Temperature", Icon::Temperature),
+    ];
+}
+
+impl FromStr for Icon {
+    type Err = String;
+
+    /// Looks up an icon by its variant name, ignoring case.
+    fn from_str(name: &str) -> Result<Icon, String> {
+        Icon::ALL.iter()
+            .find(|(icon_name, _)| icon_name.eq_ignore_ascii_case(name))
+            .map(|(_, icon)| *icon)
+            .ok_or(format!("Unknown icon: {}", name))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum Repeat {
@@ -192,28 +545,34 @@ pub enum LineContent {
 }
 
 #[derive(Serialize, Deserialize, Debug, Derivative)]
+#[derivative(Default)]
 pub struct TextModifierData {
     #[serde(rename = "has-text")]
     #[derivative(Default(value = "true"))]
     pub has_text: bool,
-    #[derivative(Default(value = ""))]
+    #[derivative(Default(value = "String::new()"))]
+    #[serde(default)]
     pub prefix: String,
-    #[derivative(Default(value = ""))]
+    #[derivative(Default(value = "String::new()"))]
+    #[serde(default)]
     pub suffix: String,
     #[derivative(Default(value = "false"))]
+    #[serde(default)]
     pub bold: bool,
     #[derivative(Default(value = "0"))]
+    #[serde(default)]
     pub wrap: i32
 }
 
 #[derive(Serialize, Deserialize, Debug, Derivative)]
+#[derivative(Default)]
 pub struct ProgressBarModifierData {
-    #[serde(rename = "has-text")]
+    #[serde(rename = "has-progress-bar")]
     #[derivative(Default(value = "true"))]
     pub has_progress_bar: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct DataAccessorData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub arg: Option<String>,
@@ -221,9 +580,632 @@ pub struct DataAccessorData {
     pub context_frame_key: Option<String>
 }
 
+impl DataAccessorData {
+    /// The `context-frame-key`, and the keys the `arg` reads as far as `arg::frame_keys` finds them.
+    fn context_frame_keys(&self) -> Vec<&str> {
+        self.context_frame_key.as_deref().into_iter()
+            .chain(self.arg.as_deref().map(crate::arg::frame_keys).unwrap_or_default())
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RangeScreenData {
     pub low: i32,
     pub high: i32,
     pub datas: Vec<ScreenFrameData>
 }
+
+/// Vibrates a mouse with a tactile motor, e.g. the Rival 600 and 700.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TactileHandler {
+    #[serde(rename = "device-type", default = "TactileHandler::default_device_type")]
+    pub device_type: String,
+    #[serde(default = "TactileHandler::default_zone")]
+    pub zone: String,
+    #[serde(default = "TactileHandler::default_mode")]
+    pub mode: String,
+    pub pattern: TactilePattern,
+    /// Plays the pattern again, otherwise it is played once per event
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rate: Option<Rate>
+}
+
+impl TactileHandler {
+    pub fn default_device_type() -> String {
+        "tactile".to_string()
+    }
+
+    pub fn default_zone() -> String {
+        "one".to_string()
+    }
+
+    pub fn default_mode() -> String {
+        "vibrate".to_string()
+    }
+
+    /// Problems the engine would reject or ignore: another device type, zone or mode than the defaults,
+    /// patterns without steps, custom vibrations of 0ms, and ranges of the pattern or rate that don't cover each value once.
+    pub fn problems(&self, min_value: i32, max_value: i32) -> Vec<String> {
+        let mut problems = vec![];
+        for (field, value, expected) in [("device-type", &self.device_type, "tactile"), ("zone", &self.zone, "one"), ("mode", &self.mode, "vibrate")] {
+            if value != expected {
+                problems.push(format!("{}: {}, tactile handlers only take {}", field, value, expected));
+            }
+        }
+
+        match &self.pattern {
+            TactilePattern::Steps(steps) => step_problems("pattern", steps, &mut problems),
+            TactilePattern::Ranges(ranges) => {
+                for (i, range) in ranges.iter().enumerate() {
+                    step_problems(&format!("pattern[{}].pattern", i), &range.pattern, &mut problems);
+                }
+                let bounds = ranges.iter().enumerate().map(|(i, range)| (i, range.low, range.high)).collect();
+                range_problems("pattern", bounds, min_value, max_value, &mut problems);
+            }
+        }
+
+        if let Some(rate) = &self.rate {
+            rate.problems(min_value, max_value, &mut problems);
+        }
+
+        problems
+    }
+}
+
+fn step_problems(path: &str, steps: &[TactileStep], problems: &mut Vec<String>) {
+    if steps.is_empty() {
+        problems.push(format!("{}: no steps", path));
+    }
+    for (i, step) in steps.iter().enumerate() {
+        if step.effect == (TactileEffect::Custom { length_ms: 0 }) {
+            problems.push(format!("{}[{}].length-ms: custom vibrations need a length above 0", path, i));
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TactilePattern {
+    Steps(Vec<TactileStep>),
+    /// A different pattern for each range of event values
+    Ranges(Vec<TactilePatternRange>)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TactilePatternRange {
+    pub low: i32,
+    pub high: i32,
+    pub pattern: Vec<TactileStep>
+}
+
+/// One vibration of a pattern, followed by an optional pause.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TactileStep {
+    #[serde(flatten)]
+    pub effect: TactileEffect,
+    #[serde(rename = "delay-ms", skip_serializing_if = "Option::is_none", default)]
+    pub delay_ms: Option<u32>
+}
+
+/// The predefined effects of the engine, from the TI haptics library, or a custom vibration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum TactileEffect {
+    #[serde(rename = "custom")]
+    Custom {
+        #[serde(rename = "length-ms")]
+        length_ms: u32
+    },
+    #[serde(rename = "ti_predefined_strongclick_100")]
+    StrongClick100,
+    #[serde(rename = "ti_predefined_strongclick_60")]
+    StrongClick60,
+    #[serde(rename = "ti_predefined_strongclick_30")]
+    StrongClick30,
+    #[serde(rename = "ti_predefined_sharpclick_100")]
+    SharpClick100,
+    #[serde(rename = "ti_predefined_sharpclick_60")]
+    SharpClick60,
+    #[serde(rename = "ti_predefined_sharpclick_30")]
+    SharpClick30,
+    #[serde(rename = "ti_predefined_softbump_100")]
+    SoftBump100,
+    #[serde(rename = "ti_predefined_softbump_60")]
+    SoftBump60,
+    #[serde(rename = "ti_predefined_softbump_30")]
+    SoftBump30,
+    #[serde(rename = "ti_predefined_doubleclick_100")]
+    DoubleClick100,
+    #[serde(rename = "ti_predefined_doubleclick_60")]
+    DoubleClick60,
+    #[serde(rename = "ti_predefined_tripleclick_100")]
+    TripleClick100,
+    #[serde(rename = "ti_predefined_softfuzz_60")]
+    SoftFuzz60,
+    #[serde(rename = "ti_predefined_strongbuzz_100")]
+    StrongBuzz100,
+    #[serde(rename = "ti_predefined_buzzalert750ms")]
+    BuzzAlert750ms,
+    #[serde(rename = "ti_predefined_buzzalert1000ms")]
+    BuzzAlert1000ms,
+    #[serde(rename = "ti_predefined_strongclick1_100")]
+    StrongClick1_100,
+    #[serde(rename = "ti_predefined_strongclick2_80")]
+    StrongClick2_80,
+    #[serde(rename = "ti_predefined_strongclick3_60")]
+    StrongClick3_60,
+    #[serde(rename = "ti_predefined_strongclick4_30")]
+    StrongClick4_30,
+    #[serde(rename = "ti_predefined_mediumclick1_100")]
+    MediumClick1_100,
+    #[serde(rename = "ti_predefined_mediumclick2_80")]
+    MediumClick2_80,
+    #[serde(rename = "ti_predefined_mediumclick3_60")]
+    MediumClick3_60,
+    #[serde(rename = "ti_predefined_sharptick1_100")]
+    SharpTick1_100,
+    #[serde(rename = "ti_predefined_sharptick2_80")]
+    SharpTick2_80,
+    #[serde(rename = "ti_predefined_sharptick3_60")]
+    SharpTick3_60
+}
+
+impl TactileEffect {
+    /// The effect as a step without a pause after it.
+    pub fn step(self) -> TactileStep {
+        TactileStep {
+            effect: self,
+            delay_ms: None
+        }
+    }
+}
+
+/// Repeats a tactile pattern or flashes a color.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Rate {
+    /// Times per second
+    pub frequency: Frequency,
+    /// Stops after this many times, goes on while the value stays the same when not set
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub repeat_limit: Option<RepeatLimit>
+}
+
+impl Rate {
+    fn problems(&self, min_value: i32, max_value: i32, problems: &mut Vec<String>) {
+        if let Frequency::Ranges(ranges) = &self.frequency {
+            let bounds = ranges.iter().enumerate().map(|(i, range)| (i, range.low, range.high)).collect();
+            range_problems("rate.frequency", bounds, min_value, max_value, problems);
+        }
+        if let Some(RepeatLimit::Ranges(ranges)) = &self.repeat_limit {
+            let bounds = ranges.iter().enumerate().map(|(i, range)| (i, range.low, range.high)).collect();
+            range_problems("rate.repeat_limit", bounds, min_value, max_value, problems);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Frequency {
+    Fixed(u32),
+    Ranges(Vec<FrequencyRange>)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FrequencyRange {
+    pub low: i32,
+    pub high: i32,
+    pub frequency: u32
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RepeatLimit {
+    Fixed(u32),
+    Ranges(Vec<RepeatLimitRange>)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RepeatLimitRange {
+    pub low: i32,
+    pub high: i32,
+    pub repeat_limit: u32
+}
+
+/// Lights a zone of a keyboard, mouse or headset.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ColorHandler {
+    /// e.g. `keyboard`, `mouse`, `headset`, `rgb-2-zone` or `rgb-per-key-zones`
+    #[serde(rename = "device-type")]
+    pub device_type: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub zone: Option<ColorZone>,
+    /// HID codes of the keys to light on `rgb-per-key-zones` devices, instead of a zone
+    #[serde(rename = "custom-zone-keys", skip_serializing_if = "Option::is_none", default)]
+    pub custom_zone_keys: Option<Vec<u8>>,
+    pub mode: ColorMode,
+    /// Not used with `ColorMode::ContextColor`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub color: Option<ColorSpec>,
+    /// Flashes the color
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rate: Option<Rate>,
+    /// Key of the color in the context frame with `ColorMode::ContextColor`
+    #[serde(rename = "context-frame-key", skip_serializing_if = "Option::is_none", default)]
+    pub context_frame_key: Option<String>
+}
+
+impl ColorHandler {
+    /// Problems the engine would reject or ignore: a zone the device type doesn't have,
+    /// a zone, color or context frame key missing for the mode, and color or rate ranges that don't cover each value once.
+    pub fn problems(&self, min_value: i32, max_value: i32) -> Vec<String> {
+        let mut problems = vec![];
+        let per_key = self.device_type == "rgb-per-key-zones";
+        let mode = kebab_name(&self.mode);
+
+        match (self.mode, &self.zone, &self.custom_zone_keys) {
+            (ColorMode::Bitmap, zone, keys) => {
+                if !per_key {
+                    problems.push(format!("mode: bitmap only works on rgb-per-key-zones devices, not {}", self.device_type));
+                }
+                if zone.is_some() || keys.is_some() {
+                    problems.push("zone: bitmap lights every key, leave out zone and custom-zone-keys".to_string());
+                }
+            },
+            (_, None, None) => problems.push("zone: no zone or custom-zone-keys".to_string()),
+            (_, Some(_), Some(_)) => problems.push("custom-zone-keys: take the place of zone, leave out one of them".to_string()),
+            (_, Some(zone), None) => {
+                if zone.is_on(&self.device_type) == Some(false) {
+                    problems.push(format!("zone: {} devices have no {} zone", self.device_type, kebab_name(zone)));
+                }
+            },
+            (_, None, Some(keys)) => {
+                if !per_key {
+                    problems.push(format!("custom-zone-keys: only rgb-per-key-zones devices light single keys, not {}", self.device_type));
+                }
+                if keys.is_empty() {
+                    problems.push("custom-zone-keys: no keys".to_string());
+                }
+            }
+        }
+
+        match (self.mode, &self.color, &self.context_frame_key) {
+            (ColorMode::ContextColor, _, None) => problems.push("context-frame-key: context-color reads the color from this key".to_string()),
+            (ColorMode::ContextColor | ColorMode::Bitmap, Some(_), _) => problems.push(format!("color: not used with the {} mode", mode)),
+            (ColorMode::Color | ColorMode::Percent | ColorMode::Count, None, _) => problems.push(format!("color: the {} mode needs a color", mode)),
+            _ => {}
+        }
+
+        if let Some(color) = &self.color {
+            color.problems("color", min_value, max_value, &mut problems);
+        }
+        if let Some(rate) = &self.rate {
+            rate.problems(min_value, max_value, &mut problems);
+        }
+
+        problems
+    }
+}
+
+/// Name of a unit variant as the engine knows it, e.g. `function-keys`.
+fn kebab_name(value: &impl Serialize) -> String {
+    serde_json::to_value(value).ok().and_then(|value| value.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorZone {
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Eleven,
+    Twelve,
+    Logo,
+    Wheel,
+    Base,
+    /// Headsets
+    Earcups,
+    FunctionKeys,
+    MainKeyboard,
+    Keypad,
+    NumberKeys,
+    MacroKeys,
+    QRow,
+    ARow,
+    ZRow,
+    NavCluster,
+    ArrowKeys,
+    All
+}
+
+impl ColorZone {
+    const NUMBERED: [ColorZone; 12] = [
+        ColorZone::One, ColorZone::Two, ColorZone::Three, ColorZone::Four, ColorZone::Five, ColorZone::Six,
+        ColorZone::Seven, ColorZone::Eight, ColorZone::Nine, ColorZone::Ten, ColorZone::Eleven, ColorZone::Twelve
+    ];
+
+    /// Whether devices of the type have the zone, `None` when the zones of the type aren't known here.
+    pub fn is_on(self, device_type: &str) -> Option<bool> {
+        use ColorZone::*;
+        match device_type {
+            "keyboard" | "rgb-per-key-zones" => Some(matches!(self, FunctionKeys | MainKeyboard | Keypad | NumberKeys | MacroKeys
+                | QRow | ARow | ZRow | NavCluster | ArrowKeys | All)),
+            "mouse" => Some(matches!(self, Wheel | Logo | Base)),
+            "headset" => Some(self == Earcups),
+            // `rgb-2-zone` up to `rgb-12-zone`
+            _ => {
+                let zones: usize = device_type.strip_prefix("rgb-")?.strip_suffix("-zone")?.parse().ok()?;
+                Some(ColorZone::NUMBERED.iter().take(zones).any(|zone| *zone == self))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    /// The whole zone takes the color
+    Color,
+    /// Lights a part of the zone matching the percent of the value, like a bar
+    Percent,
+    /// Lights as many keys as the value
+    Count,
+    /// Takes the color from the context frame
+    ContextColor,
+    /// Lights every key from the `bitmap` of the context frame, see `bitmap::KeyboardBitmap`
+    Bitmap
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8
+}
+
+impl Rgb {
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+    /// Turns the zone off
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+
+    pub const fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ColorSpec {
+    Static(Rgb),
+    /// Goes from one color to the other as the value goes from the min to the max
+    Gradient { gradient: Gradient },
+    /// A different color for each range of values
+    Ranges(Vec<ColorRange>)
+}
+
+impl ColorSpec {
+    fn problems(&self, path: &str, min_value: i32, max_value: i32, problems: &mut Vec<String>) {
+        let ColorSpec::Ranges(ranges) = self else { return };
+        for (i, range) in ranges.iter().enumerate() {
+            if matches!(range.color, ColorSpec::Ranges(_)) {
+                problems.push(format!("{}[{}].color: ranges can't have ranges, use a color or a gradient", path, i));
+            }
+        }
+        let bounds = ranges.iter().enumerate().map(|(i, range)| (i, range.low, range.high)).collect();
+        range_problems(path, bounds, min_value, max_value, problems);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Gradient {
+    pub zero: Rgb,
+    pub hundred: Rgb
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ColorRange {
+    pub low: i32,
+    pub high: i32,
+    pub color: ColorSpec
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::screen::{text, Range, Screen};
+    use super::*;
+
+    fn ranges(bounds: &[(i32, i32)]) -> ScreenHandler {
+        bounds.iter()
+            .fold(Screen::new(), |screen, &(low, high)| screen.range(Range::new(low, high).frame(Screen::single_line(text()))))
+            .build()
+    }
+
+    #[test]
+    fn ranges_covering_every_value_have_no_problems() {
+        assert!(ranges(&[(0, 49), (50, 100)]).problems(0, 100).is_empty());
+    }
+
+    #[test]
+    fn overlapping_ranges() {
+        assert_eq!(ranges(&[(0, 60), (50, 100)]).problems(0, 100), vec!["datas[1]: 50 to 100 overlaps another range"]);
+    }
+
+    #[test]
+    fn gaps_between_and_after_ranges() {
+        assert_eq!(ranges(&[(0, 9), (20, 49)]).problems(0, 100), vec![
+            "datas: no range covers 10 to 19",
+            "datas: no range covers 50 to 100"
+        ]);
+    }
+
+    #[test]
+    fn range_up_to_i32_max_leaves_no_gap() {
+        assert!(ranges(&[(0, i32::MAX)]).problems(0, i32::MAX).is_empty());
+        assert!(ranges(&[(i32::MIN, -1), (0, i32::MAX)]).problems(i32::MIN, i32::MAX).is_empty());
+    }
+
+    #[test]
+    fn wrap_only_on_single_line_frames() {
+        let single = Screen::new().frame(Screen::single_line(text().wrap(2))).build();
+        assert!(single.problems(0, 100).is_empty());
+
+        let multi = Screen::new().frame(Screen::multi_line().line(text()).line(text().wrap(2))).build();
+        assert_eq!(multi.problems(0, 100), vec!["datas[0].lines[1].wrap: only single line frames wrap, use 0"]);
+    }
+
+    #[test]
+    fn image_length_of_the_device() {
+        let apex = Screen::new().device(OLEDDeviceType::ApexSeries).frame(Screen::image(vec![0; 640])).build();
+        assert!(apex.problems(0, 100).is_empty());
+
+        let wrong = Screen::new().device(OLEDDeviceType::ApexSeries).frame(Screen::image(vec![0; 576])).build();
+        assert_eq!(wrong.problems(0, 100), vec!["datas[0].image-data: 576 bytes, screened-128x40 screens take 640"]);
+
+        let any = Screen::new().frame(Screen::image(vec![0; 100])).build();
+        assert_eq!(any.problems(0, 100),
+            vec!["datas[0].image-data: 100 bytes, expected 576, 640, 768 or 832 for one of the screen sizes"]);
+    }
+
+    #[test]
+    fn ranges_outside_of_the_values() {
+        assert_eq!(ranges(&[(-10, -1), (0, 100), (101, 200)]).problems(0, 100), vec![
+            "datas[0]: -10 to -1 is outside of the values 0 to 100",
+            "datas[2]: 101 to 200 is outside of the values 0 to 100"
+        ]);
+    }
+
+    #[test]
+    fn only_the_last_frame_repeats() {
+        let screen = Screen::new()
+            .frame(Screen::single_line(text()).length_millis(500).repeat(2))
+            .frame(Screen::single_line(text()).length_millis(500))
+            .build();
+        assert_eq!(screen.problems(0, 100), vec!["datas[0].repeats: only the last frame repeats"]);
+    }
+
+    #[test]
+    fn infinite_repeats_need_a_length_on_every_frame() {
+        let screen = Screen::new()
+            .frame(Screen::single_line(text()).length_millis(500))
+            .frame(Screen::single_line(text()).repeat_forever())
+            .build();
+        assert_eq!(screen.problems(0, 100),
+            vec!["datas[1].length-millis: the last frame repeats forever, every frame needs a length above 0"]);
+
+        let screen = Screen::new()
+            .frame(Screen::single_line(text()))
+            .frame(Screen::single_line(text()).length_millis(500).repeat_forever())
+            .build();
+        assert_eq!(screen.problems(0, 100),
+            vec!["datas[0].length-millis: the last frame repeats forever, every frame needs a length above 0"]);
+
+        let screen = Screen::new()
+            .frame(Screen::single_line(text()).length_millis(500))
+            .frame(Screen::single_line(text()).length_millis(500).repeat_forever())
+            .build();
+        assert!(screen.problems(0, 100).is_empty());
+    }
+
+    fn buzz(pattern: TactilePattern) -> TactileHandler {
+        TactileHandler {
+            device_type: TactileHandler::default_device_type(),
+            zone: TactileHandler::default_zone(),
+            mode: TactileHandler::default_mode(),
+            pattern,
+            rate: None
+        }
+    }
+
+    fn keys(zone: ColorZone, mode: ColorMode, color: Option<ColorSpec>) -> ColorHandler {
+        ColorHandler {
+            device_type: "keyboard".to_string(),
+            zone: Some(zone),
+            custom_zone_keys: None,
+            mode,
+            color,
+            rate: None,
+            context_frame_key: None
+        }
+    }
+
+    #[test]
+    fn tactile_pattern() {
+        assert!(crate::notify::alert_buzz().problems(0, 100).is_empty());
+        assert_eq!(buzz(TactilePattern::Steps(vec![])).problems(0, 100), vec!["pattern: no steps"]);
+        assert_eq!(buzz(TactilePattern::Steps(vec![TactileEffect::Custom { length_ms: 0 }.step()])).problems(0, 100),
+            vec!["pattern[0].length-ms: custom vibrations need a length above 0"]);
+    }
+
+    #[test]
+    fn tactile_zone_and_mode() {
+        let handler = TactileHandler {
+            zone: "two".to_string(),
+            mode: "buzz".to_string(),
+            ..buzz(TactilePattern::Steps(vec![TactileEffect::StrongClick100.step()]))
+        };
+        assert_eq!(handler.problems(0, 100), vec![
+            "zone: two, tactile handlers only take one",
+            "mode: buzz, tactile handlers only take vibrate"
+        ]);
+    }
+
+    #[test]
+    fn tactile_ranges() {
+        let range = |low, high| TactilePatternRange { low, high, pattern: vec![TactileEffect::StrongClick100.step()] };
+        assert_eq!(buzz(TactilePattern::Ranges(vec![range(0, 49), range(60, 100)])).problems(0, 100),
+            vec!["pattern: no range covers 50 to 59"]);
+
+        let handler = TactileHandler {
+            rate: Some(Rate {
+                frequency: Frequency::Ranges(vec![FrequencyRange { low: 0, high: 100, frequency: 2 }]),
+                repeat_limit: Some(RepeatLimit::Ranges(vec![RepeatLimitRange { low: 0, high: 50, repeat_limit: 3 }]))
+            }),
+            ..buzz(TactilePattern::Steps(vec![TactileEffect::StrongClick100.step()]))
+        };
+        assert_eq!(handler.problems(0, 100), vec!["rate.repeat_limit: no range covers 51 to 100"]);
+    }
+
+    #[test]
+    fn color_zone_of_the_device() {
+        assert!(crate::bitmap::bitmap_handler().problems(0, 100).is_empty());
+        assert!(keys(ColorZone::FunctionKeys, ColorMode::Percent, Some(ColorSpec::Static(Rgb::RED))).problems(0, 100).is_empty());
+
+        assert_eq!(keys(ColorZone::Earcups, ColorMode::Percent, Some(ColorSpec::Static(Rgb::RED))).problems(0, 100),
+            vec!["zone: keyboard devices have no earcups zone"]);
+        let three = ColorHandler { device_type: "rgb-3-zone".to_string(), ..keys(ColorZone::Four, ColorMode::Color, Some(ColorSpec::Static(Rgb::RED))) };
+        assert_eq!(three.problems(0, 100), vec!["zone: rgb-3-zone devices have no four zone"]);
+
+        let bitmap = ColorHandler { zone: Some(ColorZone::All), ..crate::bitmap::bitmap_handler() };
+        assert_eq!(bitmap.problems(0, 100), vec!["zone: bitmap lights every key, leave out zone and custom-zone-keys"]);
+    }
+
+    #[test]
+    fn color_of_the_mode() {
+        assert_eq!(keys(ColorZone::All, ColorMode::Count, None).problems(0, 100), vec!["color: the count mode needs a color"]);
+        assert_eq!(keys(ColorZone::All, ColorMode::ContextColor, None).problems(0, 100),
+            vec!["context-frame-key: context-color reads the color from this key"]);
+
+        let context = ColorHandler { context_frame_key: Some("color".to_string()), ..keys(ColorZone::All, ColorMode::ContextColor, None) };
+        assert!(context.problems(0, 100).is_empty());
+    }
+
+    #[test]
+    fn color_ranges() {
+        let range = |low, high| ColorRange { low, high, color: ColorSpec::Static(Rgb::RED) };
+        let handler = keys(ColorZone::All, ColorMode::Color, Some(ColorSpec::Ranges(vec![range(0, 10), range(5, 100)])));
+        assert_eq!(handler.problems(0, 100), vec!["color[1]: 5 to 100 overlaps another range"]);
+
+        let nested = ColorRange { low: 11, high: 100, color: ColorSpec::Ranges(vec![range(11, 100)]) };
+        let handler = keys(ColorZone::All, ColorMode::Color, Some(ColorSpec::Ranges(vec![range(0, 10), nested])));
+        assert_eq!(handler.problems(0, 100), vec!["color[1].color: ranges can't have ranges, use a color or a gradient"]);
+    }
+}
@@ -0,0 +1,97 @@
+//! `#[derive(EventPayload)]` and the handler checks of `payload`.
+#![cfg(feature = "derive")]
+
+use sse_oled_controller::api_types::{C2SGameEventCreate, EventValue};
+use sse_oled_controller::arg::Arg;
+use sse_oled_controller::payload::{self, EventPayload};
+use sse_oled_controller::screen::{progress_bar, text, Screen, Text};
+use sse_oled_controller::types::{Handler, Icon};
+
+#[derive(EventPayload)]
+struct TrackUpdate {
+    #[payload(value)]
+    progress: i32,
+    title: String,
+    #[payload(rename = "artist-name")]
+    artist: Option<String>
+}
+
+#[derive(EventPayload)]
+struct NoValue {
+    label: String
+}
+
+fn track() -> TrackUpdate {
+    TrackUpdate {
+        progress: 42,
+        title: "Song".to_string(),
+        artist: Some("Band".to_string())
+    }
+}
+
+fn screen(line: Text) -> Vec<Handler> {
+    vec![Screen::new().frame(Screen::multi_line().line(line).line(progress_bar())).into()]
+}
+
+fn event() -> C2SGameEventCreate {
+    C2SGameEventCreate {
+        game: "TEST".to_string(),
+        event: "TRACK".to_string(),
+        min_value: 0,
+        max_value: 100,
+        icon_id: Icon::Music,
+        value_optional: false
+    }
+}
+
+#[test]
+fn value_field_is_the_event_value() {
+    let data = track().to_event_data();
+    assert_eq!(data.value, EventValue::Number(42));
+
+    let frame = data.frame.unwrap();
+    assert!(!frame.contains_key("progress"));
+    assert_eq!(frame.get("title"), Some(&EventValue::String("Song".to_string())));
+}
+
+#[test]
+fn value_is_zero_without_a_value_field() {
+    let data = NoValue { label: "A".to_string() }.to_event_data();
+    assert_eq!(data.value, EventValue::Number(0));
+    assert_eq!(NoValue::KEYS, ["label"]);
+}
+
+#[test]
+fn renamed_keys() {
+    assert_eq!(TrackUpdate::KEYS, ["title", "artist-name"]);
+
+    let frame = track().to_event_data().frame.unwrap();
+    assert!(frame.contains_key("artist-name"));
+    assert!(!frame.contains_key("artist"));
+}
+
+#[test]
+fn none_is_left_out() {
+    let frame = TrackUpdate { artist: None, ..track() }.to_event_data().frame.unwrap();
+    assert!(!frame.contains_key("artist-name"));
+}
+
+#[test]
+fn known_keys_pass() {
+    assert_eq!(TrackUpdate::check_handlers(&screen(text().frame_key("artist-name"))), Ok(()));
+    assert_eq!(TrackUpdate::check_handlers(&screen(text().arg(Arg::frame_key("title")))), Ok(()));
+    assert!(payload::bind::<TrackUpdate>(event(), screen(text().frame_key("title"))).is_ok());
+}
+
+#[test]
+fn missing_keys_are_reported() {
+    let error = TrackUpdate::check_handlers(&screen(text().frame_key("artist"))).unwrap_err();
+    assert!(error.ends_with("has no context frame key artist, expected one of title, artist-name"), "{}", error);
+
+    // Also when read by an arg
+    let arg = Arg::concat([Arg::frame_key("title"), Arg::frame_key("album")]);
+    let error = TrackUpdate::check_handlers(&screen(text().arg(arg))).unwrap_err();
+    assert!(error.contains("has no context frame key album,"), "{}", error);
+
+    assert!(payload::bind::<TrackUpdate>(event(), screen(text().frame_key("progress"))).is_err());
+}